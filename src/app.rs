@@ -1,4 +1,6 @@
-use crate::searchbar::SearchBar;
+use crate::searchbar::{CursorDirection, SearchBar};
+use crate::completion::{Completion, Suggestions};
+use crate::events::Event;
 
 use dictproto::{
     Definition,
@@ -7,27 +9,59 @@ use dictproto::{
     Match,
     url::{DICTUrl, DICTUrlAccess},
     reply::Reply,
+    transcript::Recorder,
+    client::DICTClient,
+    pool::{DICTPool, PoolConfig},
     connection::*
 };
-use std::convert::Into;
 
-use std::net::TcpStream;
+use crate::bookmarks::{self, Bookmark, Bookmarks, DEFAULT_LIST};
+use crate::cache::{self, Cache};
+use crate::config::Config;
+use crate::find;
+use crate::history::{History, HistoryMovement, HistorySearch};
+use crate::links::{self, Link};
+use crate::navigation::Navigation;
+use crate::quiz::{self, Card, Deck, Review};
+use crate::worker::{Answer, Outcome, Request, Worker};
 
-#[derive(Debug, Clone, Copy)]
+use std::fs;
+use std::ops::Deref;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum AppMode {
     Define,
     Match,
+    Info,
+    Command,
+    Bookmarks,
+    Quiz
+}
+
+/// Where the moves and Enter go, when not to the search bar
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Focus {
+    SearchBar,
+    Definition,
+    Matches,
+    Databases,
     Strategies,
-    Databases
+    WordLists,
+    Bookmarks
 }
 
-impl Into<usize> for AppMode {
-    fn into(self) -> usize {
-        match self {
-            Self::Define => 0,
-            Self::Match => 1,
-            Self::Strategies => 2,
-            Self::Databases => 3
+impl From<AppMode> for usize {
+    fn from(mode: AppMode) -> usize {
+        match mode {
+            AppMode::Define => 0,
+            AppMode::Match => 1,
+            AppMode::Info => 2,
+            AppMode::Command => 3,
+            AppMode::Bookmarks => 4,
+            AppMode::Quiz => 5
         }
     }
 }
@@ -37,53 +71,331 @@ impl AppMode {
         match self {
             Self::Define => "Define",
             Self::Match => "Match",
-            Self::Strategies => "Strategies",
-            Self::Databases => "Databases"
+            Self::Info => "Info",
+            Self::Command => "Command",
+            Self::Bookmarks => "Bookmarks",
+            Self::Quiz => "Quiz"
         }
     }
 
     pub fn next(&self) -> Self {
         match self {
             Self::Define => Self::Match,
-            Self::Match => Self::Strategies,
-            Self::Strategies => Self::Databases,
-            Self::Databases => Self::Define
+            Self::Match => Self::Info,
+            Self::Info => Self::Command,
+            Self::Command => Self::Bookmarks,
+            Self::Bookmarks => Self::Quiz,
+            Self::Quiz => Self::Define
         }
     }
 
     pub fn previous(&self) -> Self {
         match self {
-            Self::Define => Self::Databases,
+            Self::Define => Self::Quiz,
+            Self::Quiz => Self::Bookmarks,
+            Self::Bookmarks => Self::Command,
+            Self::Command => Self::Info,
             Self::Match => Self::Define,
-            Self::Strategies => Self::Match,
-            Self::Databases => Self::Strategies
+            Self::Info => Self::Match
         }
     }
 
     pub fn values() -> Vec<Self> {
-        vec![AppMode::Define, AppMode::Match, AppMode::Strategies, AppMode::Databases]
+        vec![AppMode::Define, AppMode::Match, AppMode::Info, AppMode::Command, AppMode::Bookmarks, AppMode::Quiz]
+    }
+}
+
+/// Something that was found on one of the servers
+#[derive(Clone)]
+pub struct OnServer<T> {
+    pub server: usize,
+    pub item: T
+}
+
+impl<T> Deref for OnServer<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.item
+    }
+}
+
+/// A raw command, and everything the server answered to it
+pub struct Exchange {
+    pub server: usize,
+    pub line: String,
+    pub answers: Vec<RawAnswer>
+}
+
+impl Exchange {
+    /// Number of lines needed to display it
+    pub fn height(&self) -> usize {
+        1 + self.answers.iter()
+            .map(|answer| match answer {
+                RawAnswer::Reply(_) => 1,
+                RawAnswer::Text(text) => text.len()
+            })
+            .sum::<usize>()
     }
 }
 
+pub struct Server {
+    pub name: String,
+    pub databases: Vec<Database>,
+    pub stategies: Vec<Strategy>,
+    pub last_status: Option<Reply>,
+    pub last_error: Option<String>,
+    pub reconnects: usize,
+    pub msg_id: String,
+    pub capabilities: Vec<String>,
+
+    // Info mode
+    pub server_info: Vec<String>,
+    pub stats: Option<Reply>,
+    pub db_info: Vec<String>,
+    pub selected_db: usize,
+    pub selected_strat: usize,
+    info_loaded: bool,
+    // Definitions found in the cache instead
+    cached: Option<usize>,
+
+    active: (String, u16),
+    worker: Worker,
+    client: String,
+    timeouts: Timeouts,
+    // For background queries, on the active server
+    pool: Option<(String, u16, DICTPool)>
+}
+
+impl Server {
+    // The first url is the server, the others its mirrors
+    fn new(index: usize, urls: &[DICTUrl], recorder: Option<Recorder>, config: &Config, events: Sender<Event>) -> Self {
+        let url = &urls[0];
+        let name = server_name(&url.host, url.port);
+        let timeouts = config.timeouts.timeouts();
+
+        let mut conn = DICTClient::new(&url.host, url.port)
+            .client_name(&config.client)
+            .timeouts(timeouts);
+        for mirror in &urls[1..] {
+            conn = conn.mirror(&mirror.host, mirror.port);
+        }
+        if let Some(recorder) = recorder {
+            conn = conn.recorder(recorder);
+        }
+
+        Server {
+            name,
+            databases: Vec::new(),
+            stategies: Vec::new(),
+            last_status: None,
+            last_error: None,
+            reconnects: 0,
+            msg_id: String::new(),
+            capabilities: Vec::new(),
+            server_info: Vec::new(),
+            stats: None,
+            db_info: Vec::new(),
+            selected_db: 0,
+            selected_strat: 0,
+            info_loaded: false,
+            cached: None,
+            active: (url.host.clone(), url.port),
+            worker: Worker::spawn(index, conn, events),
+            client: config.client.clone(),
+            timeouts,
+            pool: None
+        }
+    }
+
+    // Keeps the status of the command, and what describes the server.
+    // Returns what is to be displayed.
+    fn answered(&mut self, outcome: Outcome) -> Option<Answer> {
+        let session = outcome.session;
+        self.reconnects = session.reconnects;
+        self.active = session.active;
+        self.msg_id = session.msg_id;
+        self.capabilities = session.capabilities;
+        self.last_error = None;
+
+        let (answer, reply) = match outcome.answer {
+            Ok(answer) => answer,
+            Err(DICTError::UnexpectedPacket(DICTPacket(_, r)))
+                | Err(DICTError::SystemError(r)) => {
+                self.last_status = Some(r);
+                return None;
+            }
+            Err(e) => {
+                self.last_status = None;
+                self.last_error = Some(e.to_string());
+                return None;
+            }
+        };
+        self.last_status = Some(reply.clone());
+
+        match answer {
+            Answer::Banner => {},
+            Answer::Databases(dbs) => {
+                self.databases = dbs;
+                if self.selected_db >= self.databases.len() {
+                    self.selected_db = 0;
+                }
+            },
+            Answer::Strategies(strats) => {
+                self.stategies = strats;
+                if self.selected_strat >= self.stategies.len() {
+                    self.selected_strat = 0;
+                }
+            },
+            Answer::ServerInfo(text) => {
+                self.server_info = text;
+            },
+            Answer::Stats => {
+                self.stats = Some(reply);
+            },
+            Answer::DatabaseInfo(db, text) => {
+                // Unless another one got selected meanwhile
+                if self.databases.get(self.selected_db).map_or(false, |d| d.name == db.name) {
+                    self.db_info = text;
+                }
+            },
+            answer => return Some(answer)
+        }
+
+        None
+    }
+
+    pub fn status_text(&self) -> String {
+        if let Some(found) = self.cached {
+            return format!("[cached] {} definition(s)", found);
+        }
+
+        let status = match (&self.last_error, &self.last_status) {
+            (Some(err), _) => err.to_owned(),
+            (None, Some(reply)) => reply.to_string(),
+            (None, None) => String::from("No status")
+        };
+
+        let status = if self.reconnects > 0 {
+            format!("[reconnected after {} attempt(s)] {}", self.reconnects, status)
+        } else {
+            status
+        };
+
+        match self.mirror() {
+            Some(mirror) => format!("[via {}] {}", mirror, status),
+            None => status
+        }
+    }
+
+    /// The mirror in use, if the server itself is down
+    pub fn mirror(&self) -> Option<String> {
+        let active = server_name(&self.active.0, self.active.1);
+
+        if active != self.name {
+            Some(active)
+        } else {
+            None
+        }
+    }
+
+    fn pool(&mut self) -> DICTPool {
+        let (ref host, port) = self.active;
+
+        match self.pool {
+            Some((ref h, p, ref pool)) if h == host && p == port => pool.clone(),
+            _ => {
+                let config = PoolConfig {
+                    timeouts: self.timeouts,
+                    client: self.client.clone(),
+                    ..PoolConfig::default()
+                };
+                let pool = DICTPool::new(host, port, config);
+                self.pool = Some((host.to_owned(), port, pool.clone()));
+                pool
+            }
+        }
+    }
+}
+
+// What a running request shows once complete
+enum Expected {
+    Definitions,
+    Matches,
+    Nothing
+}
+
+struct Running {
+    id: usize,
+    started: Instant,
+    expected: Expected,
+    // The search bar, when it was sent
+    searched: String,
+    // Those still working on it
+    servers: Vec<usize>,
+    // The database and the word, to cache the definitions found
+    looked_up: Option<(String, String)>,
+    // The answer to a quiz card, kept out of the history
+    quiz: bool
+}
+
+// A definition shown, to go back to it
+#[derive(Clone)]
+struct View {
+    searched: String,
+    word: String,
+    results: Vec<OnServer<Definition>>,
+    selected_def: usize,
+    scroll_amount: u16
+}
+
 pub struct App {
     pub searched: SearchBar,
 
     // To display things
-    pub results: Vec<Definition>,
-    pub last_status: Option<Reply>,
-    pub databases: Vec<Database>,
-    pub stategies: Vec<Strategy>,
-    pub matches: Vec<Match>,
+    pub results: Vec<OnServer<Definition>>,
+    pub matches: Vec<OnServer<Match>>,
+    pub commands: Vec<Exchange>,
+    pub servers: Vec<Server>,
+    pub completion: Option<Completion>,
+    pub suggestions: Suggestions,
+    pub bookmarks: Bookmarks,
+    pub deck: Deck,
 
-    msg_id: String,
+    cache: Cache,
+    config: Config,
+    events: Sender<Event>,
+    queries: usize,
+    requests: usize,
+    running: Option<Running>,
+    found_defs: Vec<OnServer<Definition>>,
+    found_matches: Vec<OnServer<Match>>,
+    current_server: usize,
+    all_servers: bool,
     mode: AppMode,
+    focus: Focus,
     history: History,
+    history_search: Option<HistorySearch>,
+    // What is typed after /, and what was looked for last
+    find: Option<String>,
+    found: Option<String>,
+    visited: Navigation<View>,
     selected_def: usize,
-    scroll_amount: u16,
-    conn: DICTConnection
+    selected_link: Option<usize>,
+    selected_match: usize,
+    // The word the definitions shown are for
+    word: String,
+    word_list: String,
+    selected_list: usize,
+    selected_bookmark: usize,
+    review: Option<Review>,
+    // Shown in the status bar instead, until the next command
+    notice: Option<String>,
+    scroll_amount: u16
 }
 
-fn parse_search_bar(src: &String) -> (String, Database, Strategy) {
+// Without @database or :strategy, those of the configuration
+fn parse_search_bar(src: &str, config: &Config) -> (String, Database, Strategy) {
     let mut word: String = String::with_capacity(src.len());
     let mut db: Option<Database> = None;
     let mut strat: Option<Strategy> = None;
@@ -98,7 +410,7 @@ fn parse_search_bar(src: &String) -> (String, Database, Strategy) {
             }
             _ => {
                 if !word.is_empty() {
-                    word.push_str(" ");
+                    word.push(' ');
                 }
                 word.push_str(part);
             },
@@ -106,48 +418,115 @@ fn parse_search_bar(src: &String) -> (String, Database, Strategy) {
     }
 
     (word,
-     db.or(Some(Database::all())).unwrap(),
-     strat.or(Some(Strategy::default())).unwrap())
+     db.unwrap_or_else(|| Database::from(config.database.clone())),
+     strat.unwrap_or_else(|| Strategy::from(config.strategy.clone())))
+}
+
+fn match_words(pool: &DICTPool, db: Database, strat: Strategy, word: String) -> Vec<String> {
+    match pool.match_db(db, strat, word) {
+        Ok((matches, _)) => matches.into_iter().map(|m| m.word).collect(),
+        Err(_) => Vec::new()
+    }
+}
+
+fn server_name(host: &str, port: u16) -> String {
+    if port == DEFAULT_PORT {
+        host.to_owned()
+    } else {
+        format!("{}:{}", host, port)
+    }
 }
 
-const SCROLL_AMOUNT: u16 = 10;
+// Index moved by steps, within a list of len items
+fn step(selected: usize, len: usize, steps: isize) -> usize {
+    let last = len.saturating_sub(1) as isize;
+    (selected as isize + steps).clamp(0, last) as usize
+}
+
+// The search bar text, with its database or strategy set to name
+fn with_option(text: &str, sigil: char, name: &str) -> String {
+    let option = format!("{}{}", sigil, name);
+    let mut parts: Vec<&str> = text.split_ascii_whitespace().collect();
+
+    match parts.iter().position(|part| part.starts_with(sigil)) {
+        Some(i) => parts[i] = &option,
+        None => parts.push(&option)
+    }
+
+    parts.join(" ")
+}
+
+// Definitions one can go back to
+const VISITED_MAX: usize = 100;
+
+const SPINNER: [&str; 4] = ["|", "/", "-", "\\"];
+
+const DEFAULT_PORT: u16 = 2628;
 
 impl App {
-    pub fn new(addr: &str) -> Self {
+    /// The recorders are those of the servers, in order
+    pub fn new(config: Config, recorders: Vec<Recorder>, history: History, bookmarks: Bookmarks,
+               deck: Deck, cache: Cache, events: Sender<Event>) -> Self {
         // Should have been checked in main
-        let url = DICTUrl::new(addr).unwrap();
-        let stream = TcpStream::connect((url.host, url.port)).expect("Invalid socket address");
-        let mut conn = DICTConnection::new(stream).unwrap();
+        let urls: Vec<Vec<DICTUrl>> = config.servers.iter()
+            .map(|addr| addr.split(',').map(|url| DICTUrl::new(url).unwrap()).collect())
+            .collect();
 
-        // TODO: Maybe things can fail here... Possibly show status on startup ?
-        let (msg_id, last_status) = conn.start().unwrap();
+        let mut recorders = recorders.into_iter();
+        let servers = urls.iter().enumerate()
+            .map(|(i, url)| Server::new(i, url, recorders.next(), &config, events.clone()))
+            .collect();
 
         let mut app = App {
             searched: SearchBar::default(),
-            results: vec![Definition::empty()],
-            databases: Vec::new(),
-            stategies: Vec::new(),
+            results: Vec::new(),
             matches: Vec::new(),
-            msg_id,
-            last_status: Some(last_status),
-            history: History::new(),
-            conn,
+            commands: Vec::new(),
+            servers,
+            completion: None,
+            suggestions: Suggestions::default(),
+            bookmarks,
+            deck,
+            cache,
+            config,
+            events,
+            queries: 0,
+            requests: 0,
+            running: None,
+            found_defs: Vec::new(),
+            found_matches: Vec::new(),
+            current_server: 0,
+            all_servers: false,
+            history,
+            history_search: None,
+            find: None,
+            found: None,
+            visited: Navigation::new(VISITED_MAX),
             mode: AppMode::Define,
+            focus: Focus::SearchBar,
             selected_def: 0,
+            selected_link: None,
+            selected_match: 0,
+            word: String::new(),
+            word_list: String::from(DEFAULT_LIST),
+            selected_list: 0,
+            selected_bookmark: 0,
+            review: None,
+            notice: None,
             scroll_amount: 0
         };
+        app.definition_reset();
 
-        app.run_client();
-        app.run_show_dbs();
-        app.run_show_strats();
+        let all = (0..app.servers.len()).collect();
+        app.request(all, Expected::Nothing, Request::Connect);
 
         // Now use the url
-        match url.access_method {
-            DICTUrlAccess::Define(word, db, _) => {
+        match urls.into_iter().flatten().next().map(|url| url.access_method) {
+            Some(DICTUrlAccess::Define(word, db, _)) => {
                 app.searched.set_text(&word);
-                app.define_internal(word, db);
+                app.define_internal(app.targets(), word, db);
             }
-            DICTUrlAccess::Match(word, db, strat, _) => {
+            Some(DICTUrlAccess::Match(word, db, strat, _)) => {
                 app.searched.set_text(&word);
                 app.match_internal(word, db, strat);
             },
@@ -157,106 +536,502 @@ impl App {
         app
     }
 
-    pub fn run_client(&mut self) {
-        self.last_status = self.conn.client("redict".to_owned()).ok();
+    // Servers
+    pub fn server(&self) -> &Server {
+        &self.servers[self.current_server]
     }
 
-    fn define_internal(&mut self, word: String, db: Database) {
-        self.history.push(self.searched.text().to_owned());
-        self.scroll_amount = 0;
-        self.selected_def = 0;
+    pub fn current_server(&self) -> usize {
+        self.current_server
+    }
 
-        let answer = self.conn.define(db, word);
+    pub fn next_server(&mut self) {
+        self.current_server = (self.current_server + 1) % self.servers.len();
+        self.mode_changed();
+    }
 
-        match answer {
-            Ok((defs, status)) => {
-                self.results = defs;
-                self.last_status = Some(status);
+    pub fn previous_server(&mut self) {
+        if self.current_server > 0 {
+            self.current_server -= 1;
+        } else {
+            self.current_server = self.servers.len() - 1;
+        }
+        self.mode_changed();
+    }
+
+    /// Whether Define and Match query all servers, or only the current one
+    pub fn all_servers(&self) -> bool {
+        self.all_servers
+    }
+
+    pub fn toggle_all_servers(&mut self) {
+        self.all_servers = !self.all_servers;
+    }
+
+    fn targets(&self) -> Vec<usize> {
+        if self.all_servers {
+            (0..self.servers.len()).collect()
+        } else {
+            vec![self.current_server]
+        }
+    }
+
+    pub fn status_text(&self) -> String {
+        let status = if let Some(ref notice) = self.notice {
+            notice.clone()
+        } else if self.all_servers && self.servers.len() > 1 {
+            self.servers.iter()
+                .map(|server| format!("[{}] {}", server.name, server.status_text()))
+                .collect::<Vec<String>>()
+                .join(" | ")
+        } else {
+            self.server().status_text()
+        };
+
+        let status = if self.cache.is_offline() {
+            format!("[offline] {}", status)
+        } else {
+            status
+        };
+
+        match self.running {
+            Some(ref running) => {
+                let elapsed = running.started.elapsed();
+                let frame = SPINNER[(elapsed.as_millis() / 100) as usize % SPINNER.len()];
+                format!("{} {:.1}s {}", frame, elapsed.as_secs_f32(), status)
             },
-            Err(DICTError::UnexpectedPacket(DICTPacket(_, r)))
-                | Err(DICTError::SystemError(r)) => {
-                self.definition_reset();
-                self.last_status = Some(r);
-            }
-            _ => {
-                self.definition_reset();
-                self.last_status = None;
-            }
+            None => status
         }
     }
 
-    pub fn run_define(&mut self) {
-        let (word, db, _) = parse_search_bar(self.searched.text());
-        self.define_internal(word, db);
+    /// Labels things by server only when there are several
+    pub fn source_label(&self, server: usize, label: &str) -> String {
+        if self.servers.len() > 1 {
+            format!("{} [{}]", label, self.servers[server].name)
+        } else {
+            label.to_owned()
+        }
     }
 
-    fn match_internal(&mut self, word: String, db: Database, strat: Strategy) {
-        self.history.push(self.searched.text().to_owned());
-        self.scroll_amount = 0;
-        self.selected_def = 0;
+    // Requests
+    fn request(&mut self, servers: Vec<usize>, expected: Expected, request: Request) {
+        self.notice = None;
+        if self.cache.is_offline() && !servers.is_empty() {
+            self.notice = Some(String::from("Nothing is sent to the servers while offline"));
+            return;
+        }
+
+        self.requests += 1;
+        for server in &mut self.servers {
+            server.cached = None;
+        }
+        for &i in &servers {
+            self.servers[i].worker.send(self.requests, request.clone());
+        }
 
-        let answer = self.conn.match_db(db, strat, word);
+        // Answers to the previous one are not shown anymore
+        self.found_defs.clear();
+        self.found_matches.clear();
+        self.running = Some(Running {
+            id: self.requests,
+            started: Instant::now(),
+            expected,
+            searched: self.searched.text().to_owned(),
+            servers,
+            looked_up: None,
+            quiz: self.mode == AppMode::Quiz
+        });
+    }
+
+    fn is_running(&self, id: usize) -> bool {
+        self.running.as_ref().map_or(false, |r| r.id == id)
+    }
+
+    pub fn answered(&mut self, server: usize, id: usize, outcome: Outcome) {
+        let answer = match self.servers[server].answered(outcome) {
+            Some(answer) => answer,
+            None => return
+        };
+
+        if !self.is_running(id) {
+            return;
+        }
 
         match answer {
-            Ok((matches, status)) => {
-                self.matches = matches;
-                self.last_status = Some(status);
+            Answer::Definitions(defs) => {
+                if let Some((database, word)) = self.running.as_ref().and_then(|r| r.looked_up.clone()) {
+                    let key = cache::Key { server: self.servers[server].name.clone(), database, word };
+                    self.cache.insert(key, defs.clone());
+                }
+                self.found_defs.extend(defs.into_iter().map(|item| OnServer { server, item }));
             },
-            Err(DICTError::UnexpectedPacket(DICTPacket(_, r)))
-                | Err(DICTError::SystemError(r)) => {
-                self.match_reset();
-                self.last_status = Some(r);
-            }
-            _ => {
+            Answer::Matches(matches) => {
+                self.found_matches.extend(matches.into_iter().map(|item| OnServer { server, item }));
+            },
+            Answer::Raw(answers) => {
+                if let Some(exchange) = self.commands.last_mut() {
+                    exchange.answers = answers;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    /// A server completed the request
+    pub fn done(&mut self, server: usize, id: usize) {
+        let running = match self.running {
+            Some(ref mut running) if running.id == id => running,
+            _ => return
+        };
+
+        running.servers.retain(|s| *s != server);
+        if running.servers.is_empty() {
+            self.finish();
+        }
+    }
+
+    // Shows what the request gave
+    fn finish(&mut self) {
+        let running = match self.running.take() {
+            Some(running) => running,
+            None => return
+        };
+
+        match running.expected {
+            Expected::Definitions => {
+                self.keep_view();
+                self.scroll_amount = 0;
+                self.selected_def = 0;
+                self.selected_link = None;
+
+                if self.found_defs.is_empty() {
+                    self.definition_reset();
+                } else {
+                    // In the order of the servers
+                    self.results = std::mem::take(&mut self.found_defs);
+                    self.results.sort_by_key(|def| def.server);
+                    self.word = parse_search_bar(&running.searched, &self.config).0;
+                }
+
+                if !running.quiz {
+                    self.visited.visit(View {
+                        searched: running.searched,
+                        word: self.word.clone(),
+                        results: self.results.clone(),
+                        selected_def: 0,
+                        scroll_amount: 0
+                    });
+                }
+
+                // Browsing from link to link
+                if self.focus == Focus::Definition {
+                    self.select_link(1);
+                }
+            },
+            Expected::Matches => {
+                self.scroll_amount = 0;
+                self.selected_match = 0;
                 self.match_reset();
-                self.last_status = None;
+                self.matches.append(&mut self.found_matches);
+                self.matches.sort_by_key(|m| m.server);
+            },
+            Expected::Nothing => {}
+        }
+    }
+
+    /// Stops the running request, returns false when there is none
+    pub fn cancel(&mut self) -> bool {
+        let running = match self.running.take() {
+            Some(running) => running,
+            None => return false
+        };
+
+        // The older requests still waiting are dropped too
+        for server in &self.servers {
+            server.worker.cancel(running.id);
+        }
+        for i in running.servers {
+            self.servers[i].last_error = Some(DICTError::Cancelled.to_string());
+        }
+
+        true
+    }
+
+    fn define_internal(&mut self, servers: Vec<usize>, word: String, db: Database) {
+        self.history.push(self.searched.text().to_owned());
+        self.suggestions.settle(self.searched.text());
+
+        self.define(servers, word, db);
+    }
+
+    // Definitions in the cache are not looked up again
+    fn define(&mut self, servers: Vec<usize>, word: String, db: Database) {
+        let mut cached = Vec::new();
+        let mut missing = Vec::new();
+        for server in servers {
+            let key = cache::Key { server: self.servers[server].name.clone(), database: db.name.clone(), word: word.clone() };
+            match self.cache.get(&key) {
+                Some(defs) => cached.push((server, defs)),
+                None if !self.cache.is_offline() => missing.push(server),
+                None => {}
             }
         }
+
+        let found = !cached.is_empty();
+        self.request(missing, Expected::Definitions, Request::Define(db.clone(), word.clone()));
+        for (server, defs) in cached {
+            self.servers[server].cached = Some(defs.len());
+            self.found_defs.extend(defs.into_iter().map(|item| OnServer { server, item }));
+        }
+
+        let running = match self.running {
+            Some(ref mut running) => running,
+            None => return
+        };
+        running.looked_up = Some((db.name, word));
+        if running.servers.is_empty() {
+            self.finish();
+        }
+
+        if self.cache.is_offline() && !found {
+            self.notice = Some(String::from("Not in the cache"));
+        }
+    }
+
+    pub fn run_define(&mut self) {
+        let (word, db, _) = parse_search_bar(self.searched.text(), &self.config);
+        self.define_internal(self.targets(), word, db);
+    }
+
+    fn match_internal(&mut self, word: String, db: Database, strat: Strategy) {
+        self.history.push(self.searched.text().to_owned());
+        self.suggestions.settle(self.searched.text());
+
+        self.request(self.targets(), Expected::Matches, Request::Match(db, strat, word));
     }
 
     pub fn run_match(&mut self) {
-        let (word, db, strat) = parse_search_bar(self.searched.text());
+        let (word, db, strat) = parse_search_bar(self.searched.text(), &self.config);
         self.match_internal(word, db, strat);
     }
 
-    pub fn run_show_dbs(&mut self) {
+    pub fn run_command(&mut self) {
+        let line = self.searched.text().to_owned();
+        if line.trim().is_empty() {
+            return;
+        }
+        self.history.push(line.clone());
+
+        // Show the new exchange at the top of the pane
+        self.scroll_amount = self.commands_height() as u16;
+        self.commands.push(Exchange {
+            server: self.current_server,
+            line: line.clone(),
+            answers: Vec::new()
+        });
+
+        self.request(vec![self.current_server], Expected::Nothing, Request::Raw(line));
+    }
+
+    fn commands_height(&self) -> usize {
+        self.commands.iter().map(Exchange::height).sum()
+    }
+
+    pub fn run_info(&mut self) {
         self.scroll_amount = 0;
-        let answer = self.conn.show_db();
 
-        match answer {
-            Ok((dbs, status)) => {
-                self.databases = dbs;
-                self.last_status = Some(status);
-            },
-            Err(DICTError::UnexpectedPacket(DICTPacket(_, r)))
-                | Err(DICTError::SystemError(r)) => {
-                self.last_status = Some(r);
+        let server = &mut self.servers[self.current_server];
+        server.info_loaded = true;
+        let request = Request::Info(server.selected_db);
+        self.request(vec![self.current_server], Expected::Nothing, request);
+    }
+
+    // Database selection, in Info mode
+    pub fn next_database(&mut self) {
+        let server = &self.servers[self.current_server];
+        if server.databases.is_empty() {
+            return;
+        }
+
+        self.select_database((server.selected_db + 1) % server.databases.len());
+    }
+
+    pub fn previous_database(&mut self) {
+        let server = &self.servers[self.current_server];
+        if server.databases.is_empty() {
+            return;
+        }
+
+        if server.selected_db > 0 {
+            self.select_database(server.selected_db - 1);
+        } else {
+            self.select_database(server.databases.len() - 1);
+        }
+    }
+
+    fn select_database(&mut self, index: usize) {
+        let server = &mut self.servers[self.current_server];
+        server.selected_db = index;
+        server.db_info.clear();
+        let db = server.databases[index].clone();
+
+        self.scroll_amount = 0;
+        self.request(vec![self.current_server], Expected::Nothing, Request::ShowInfo(db));
+    }
+
+    // Completion
+    /// Completes the word before the cursor, or cycles through the
+    /// candidates. Returns false when there is nothing to complete.
+    pub fn complete(&mut self, forward: bool) -> bool {
+        if let Some(ref mut completion) = self.completion {
+            if let Some((start, end, candidate)) = completion.cycle(forward) {
+                let candidate = candidate.to_owned();
+                self.searched.replace(start, end, &candidate);
             }
+            return true;
+        }
+
+        let start = self.searched.word_start();
+        let end = self.searched.cursor();
+        let word = self.searched.slice(start, end).to_owned();
+        if word.is_empty() {
+            return false;
+        }
+
+        let server = self.server();
+        let completion = match word.get(0..1) {
+            Some("@") => {
+                let names = server.databases.iter().map(|db| db.name.as_str());
+                Completion::new(start, end, Completion::filter("@", &word[1..], names))
+            },
+            Some(":") => {
+                let names = server.stategies.iter().map(|strat| strat.name.as_str());
+                Completion::new(start, end, Completion::filter(":", &word[1..], names))
+            },
             _ => {
-                self.last_status = None;
+                self.queries += 1;
+                self.query_completions(word);
+                Completion::pending(start, end, self.queries)
             }
+        };
+
+        let pending = completion.query.is_some();
+        self.completion = Some(completion);
+        if !pending {
+            self.complete(forward);
         }
+
+        true
     }
 
-    pub fn run_show_strats(&mut self) {
-        self.scroll_amount = 0;
-        let answer = self.conn.show_strat();
+    // Words starting with word, looked up off the input thread
+    fn query_completions(&mut self, word: String) {
+        if self.cache.is_offline() {
+            self.events.send(Event::Completions(self.queries, Vec::new())).ok();
+            return;
+        }
 
-        match answer {
-            Ok((strats, status)) => {
-                self.stategies = strats;
-                self.last_status = Some(status);
-            },
-            Err(DICTError::UnexpectedPacket(DICTPacket(_, r)))
-                | Err(DICTError::SystemError(r)) => {
-                self.last_status = Some(r);
+        let (_, db, _) = parse_search_bar(self.searched.text(), &self.config);
+        let pool = self.servers[self.current_server].pool();
+        let events = self.events.clone();
+        let query = self.queries;
+
+        thread::spawn(move || {
+            let words = match_words(&pool, db, Strategy::prefix(), word);
+            events.send(Event::Completions(query, words)).ok();
+        });
+    }
+
+    pub fn completions_found(&mut self, query: usize, words: Vec<String>) {
+        if let Some(ref mut completion) = self.completion {
+            if completion.query == Some(query) {
+                completion.found(words);
             }
-            _ => {
-                self.last_status = None;
+        }
+    }
+
+    pub fn close_completion(&mut self) {
+        self.completion = None;
+    }
+
+    // Suggestions
+    /// Looks up suggestions once the searched word stays still
+    pub fn tick(&mut self) {
+        if !matches!(self.mode, AppMode::Define | AppMode::Match)
+            || self.cache.is_offline()
+            || self.focus != Focus::SearchBar
+            || self.history_search.is_some()
+            || !self.suggestions.update(self.searched.text()) {
+            return;
+        }
+
+        let (word, db, _) = parse_search_bar(self.searched.text(), &self.config);
+        if word.is_empty() {
+            return;
+        }
+
+        let pool = self.servers[self.current_server].pool();
+        let events = self.events.clone();
+        let query = self.suggestions.next_query();
+
+        thread::spawn(move || {
+            let mut words = match_words(&pool, db.clone(), Strategy::prefix(), word.clone());
+            if words.is_empty() {
+                // Probably misspelled
+                words = match_words(&pool, db, Strategy::from(String::from("lev")), word);
             }
+            events.send(Event::Suggestions(query, words)).ok();
+        });
+    }
+
+    /// How long the main loop may wait for events
+    pub fn timeout(&self) -> Option<Duration> {
+        // Keep the spinner going
+        let spinner = self.running.as_ref().map(|_| Duration::from_millis(100));
+
+        match (self.suggestions.timeout(), spinner) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b)
         }
     }
 
+    /// Whether characters are typed in the search bar, rather than bound
+    /// to actions
+    pub fn typing(&self) -> bool {
+        self.mode != AppMode::Quiz && self.focus == Focus::SearchBar
+    }
+
+    pub fn suggesting(&self) -> bool {
+        !self.suggestions.words.is_empty() && self.completion.is_none()
+    }
+
+    /// Defines the selected suggestion, keeping the database and strategy
+    pub fn pick_suggestion(&mut self) {
+        let text = match self.suggestions.selected() {
+            Some(word) => self.with_options(word),
+            None => return
+        };
+
+        self.searched.set_text(&text);
+        self.searched.move_cursor(CursorDirection::Last);
+        self.set_mode(AppMode::Define);
+        self.run_define();
+    }
+
+    // The word, with the database and strategy of the search bar
+    fn with_options(&self, word: &str) -> String {
+        let mut text = word.to_owned();
+
+        for part in self.searched.text().split_ascii_whitespace() {
+            if part.starts_with('@') || part.starts_with(':') {
+                text.push(' ');
+                text.push_str(part);
+            }
+        }
+
+        text
+    }
+
     // Definition selection
     pub fn selected_def(&self) -> usize {
         self.selected_def
@@ -268,8 +1043,12 @@ impl App {
     }
 
     fn definition_reset(&mut self) {
+        self.word.clear();
+        self.selected_def = 0;
+        self.selected_link = None;
+        self.scroll_amount = 0;
         self.results.truncate(0);
-        self.results.push(Definition::empty());
+        self.results.push(OnServer { server: self.current_server, item: Definition::empty() });
     }
 
     fn match_reset(&mut self) {
@@ -278,6 +1057,7 @@ impl App {
 
     pub fn next_definition(&mut self) {
         self.selected_def = (self.selected_def + 1) % self.results.len();
+        self.selected_link = None;
         self.scroll_amount = 0;
     }
 
@@ -287,32 +1067,123 @@ impl App {
         } else {
             self.selected_def = self.results.len() - 1;
         }
+        self.selected_link = None;
         self.scroll_amount = 0;
     }
 
+    // Cross-references
+    /// Those of the shown definition
+    pub fn links(&self) -> Vec<Link> {
+        links::parse(&self.results[self.selected_def].text)
+    }
+
+    pub fn selected_link(&self) -> Option<usize> {
+        self.selected_link
+    }
+
+    fn select_link(&mut self, steps: isize) {
+        let links = self.links();
+        if links.is_empty() {
+            return;
+        }
+
+        let len = links.len() as isize;
+        let selected = match self.selected_link {
+            Some(i) => (i as isize + steps).rem_euclid(len) as usize,
+            None if steps > 0 => 0,
+            None => links.len() - 1
+        };
+        self.selected_link = Some(selected);
+
+        // Keep it in view
+        let line = links[selected].start.0 as u16;
+        if line < self.scroll_amount || line >= self.scroll_amount.saturating_add(self.config.scroll) {
+            self.scroll_amount = line;
+        }
+    }
+
+    // Defines the selected cross-reference
+    fn follow_link(&mut self) {
+        let link = match self.selected_link.and_then(|i| self.links().into_iter().nth(i)) {
+            Some(link) => link,
+            None => return
+        };
+
+        let text = self.with_options(&link.word);
+        self.searched.set_text(&text);
+        self.searched.move_cursor(CursorDirection::Last);
+        self.run_define();
+    }
+
     // Definition scrolling
     pub fn definition_scroll(&self) -> u16 {
         self.scroll_amount
     }
 
-    pub fn scroll_down(&mut self) {
-        let max_scroll = match self.mode {
-            AppMode::Define => self.results[self.selected_def].text.len(),
+    // Lines of the text scrolled
+    fn text_height(&self) -> usize {
+        match self.mode {
+            AppMode::Define | AppMode::Quiz => self.results[self.selected_def].text.len(),
             AppMode::Match => self.matches.len(),
-            _ => 0
-        };
+            AppMode::Info => self.server().db_info.len(),
+            AppMode::Command => self.commands_height(),
+            AppMode::Bookmarks => self.selected_entry().map_or(0, |b| b.definition.text.len())
+        }
+    }
+
+    pub fn scroll_down(&mut self) {
+        // The list follows its selection
+        if let AppMode::Match = self.mode {
+            self.selected_match = step(self.selected_match, self.matches.len(), self.config.scroll as isize);
+            return;
+        }
 
-        if ((self.scroll_amount  + SCROLL_AMOUNT) as usize) < max_scroll {
-            self.scroll_amount += SCROLL_AMOUNT;
+        let scrolled = self.scroll_amount.saturating_add(self.config.scroll);
+        if (scrolled as usize) < self.text_height() {
+            self.scroll_amount = scrolled;
         }
     }
 
     pub fn scroll_up(&mut self) {
-        if self.scroll_amount >= SCROLL_AMOUNT {
-            self.scroll_amount -= SCROLL_AMOUNT;
+        if let AppMode::Match = self.mode {
+            self.selected_match = step(self.selected_match, self.matches.len(), -(self.config.scroll as isize));
+            return;
+        }
+
+        if self.scroll_amount >= self.config.scroll {
+            self.scroll_amount -= self.config.scroll;
+        }
+    }
+
+    /// Scrolls by lines, down when positive
+    pub fn scroll_lines(&mut self, lines: isize) {
+        if let AppMode::Match = self.mode {
+            self.selected_match = step(self.selected_match, self.matches.len(), lines);
+            return;
+        }
+
+        self.scroll_amount = step(self.scroll_amount as usize, self.text_height(), lines) as u16;
+    }
+
+    pub fn scroll_top(&mut self) {
+        match self.mode {
+            AppMode::Match => self.selected_match = 0,
+            _ => self.scroll_amount = 0
         }
     }
 
+    // As far as scrolling down goes
+    pub fn scroll_bottom(&mut self) {
+        if let AppMode::Match = self.mode {
+            self.selected_match = self.matches.len().saturating_sub(1);
+            return;
+        }
+
+        let height = self.text_height();
+        let steps = height.saturating_sub(1) / self.config.scroll.max(1) as usize;
+        self.scroll_amount = (steps * self.config.scroll as usize) as u16;
+    }
+
     // Mode
     pub fn mode(&self) -> AppMode {
         self.mode
@@ -320,6 +1191,352 @@ impl App {
 
     pub fn set_mode(&mut self, new: AppMode) {
         self.mode = new;
+        self.focus = Focus::SearchBar;
+        self.notice = None;
+        self.scroll_amount = 0;
+        self.mode_changed();
+    }
+
+    // Info is only fetched when first shown, cards due when reviewing
+    fn mode_changed(&mut self) {
+        match self.mode {
+            AppMode::Info if !self.server().info_loaded => {
+                self.run_info();
+            },
+            AppMode::Quiz => {
+                self.start_review();
+            },
+            _ => {}
+        }
+    }
+
+    // Lists
+    pub fn focus(&self) -> Focus {
+        self.focus
+    }
+
+    pub fn selected_match(&self) -> usize {
+        self.selected_match
+    }
+
+    /// Gives the focus to the next list of the mode, then back to the
+    /// search bar
+    pub fn next_focus(&mut self) {
+        self.focus = match (self.mode, self.focus) {
+            (AppMode::Define, Focus::SearchBar) => Focus::Definition,
+            (AppMode::Match, Focus::SearchBar) => Focus::Matches,
+            (AppMode::Info, Focus::SearchBar) => Focus::Databases,
+            (AppMode::Info, Focus::Databases) => Focus::Strategies,
+            (AppMode::Bookmarks, Focus::SearchBar) => Focus::WordLists,
+            (AppMode::Bookmarks, Focus::WordLists) => Focus::Bookmarks,
+            _ => Focus::SearchBar
+        };
+        self.suggestions.close();
+
+        if self.focus == Focus::Definition {
+            self.selected_link = None;
+            self.select_link(1);
+        }
+    }
+
+    pub fn unfocus(&mut self) {
+        self.focus = Focus::SearchBar;
+    }
+
+    /// Moves the selection of the focused list by steps
+    pub fn select(&mut self, steps: isize) {
+        match self.focus {
+            Focus::SearchBar => {},
+            Focus::Definition => {
+                self.select_link(steps);
+            },
+            Focus::Matches => {
+                self.selected_match = step(self.selected_match, self.matches.len(), steps);
+            },
+            Focus::Databases => {
+                let server = self.server();
+                let selected = step(server.selected_db, server.databases.len(), steps);
+                if selected != server.selected_db {
+                    self.select_database(selected);
+                }
+            },
+            Focus::Strategies => {
+                let server = &mut self.servers[self.current_server];
+                server.selected_strat = step(server.selected_strat, server.stategies.len(), steps);
+            },
+            Focus::WordLists => {
+                self.selected_list = step(self.selected_list, self.bookmarks.lists.len(), steps);
+                self.selected_bookmark = 0;
+                self.scroll_amount = 0;
+            },
+            Focus::Bookmarks => {
+                let len = self.bookmarks.lists.get(self.selected_list).map_or(0, |list| list.entries.len());
+                self.selected_bookmark = step(self.selected_bookmark, len, steps);
+                self.scroll_amount = 0;
+            }
+        }
+    }
+
+    /// Uses the selected item of the focused list
+    pub fn pick(&mut self) {
+        match self.focus {
+            Focus::SearchBar => {},
+            Focus::Definition => {
+                self.follow_link();
+            },
+            Focus::Matches => {
+                let m = match self.matches.get(self.selected_match) {
+                    Some(m) => m,
+                    None => return
+                };
+                let (server, word, db) = (m.server, m.word.clone(), m.source.clone());
+
+                self.searched.set_text(&format!("{} @{}", word, db.name));
+                self.searched.move_cursor(CursorDirection::Last);
+                self.set_mode(AppMode::Define);
+                self.define_internal(vec![server], word, db);
+            },
+            Focus::Databases => {
+                let server = self.server();
+                if let Some(db) = server.databases.get(server.selected_db) {
+                    let text = with_option(self.searched.text(), '@', &db.name);
+                    self.set_search(text);
+                }
+            },
+            Focus::Strategies => {
+                let server = self.server();
+                if let Some(strat) = server.stategies.get(server.selected_strat) {
+                    let text = with_option(self.searched.text(), ':', &strat.name);
+                    self.set_search(text);
+                }
+            },
+            Focus::WordLists => {
+                if let Some(list) = self.bookmarks.lists.get(self.selected_list) {
+                    self.word_list = list.name.clone();
+                    self.notice = Some(format!("Bookmarks now go to {}", self.word_list));
+                }
+            },
+            Focus::Bookmarks => {
+                self.open_bookmark();
+            }
+        }
+    }
+
+    fn set_search(&mut self, text: String) {
+        self.searched.set_text(&text);
+        self.searched.move_cursor(CursorDirection::Last);
+        self.unfocus();
+    }
+
+    // Navigation between the definitions shown
+    pub fn back(&mut self) {
+        self.keep_view();
+        if let Some(view) = self.visited.back().cloned() {
+            self.show_view(view);
+        }
+    }
+
+    pub fn forward(&mut self) {
+        self.keep_view();
+        if let Some(view) = self.visited.forward().cloned() {
+            self.show_view(view);
+        }
+    }
+
+    // Where the definition shown was left
+    fn keep_view(&mut self) {
+        if !matches!(self.mode, AppMode::Define) {
+            return;
+        }
+
+        let (selected_def, scroll_amount) = (self.selected_def, self.scroll_amount);
+        if let Some(view) = self.visited.current_mut() {
+            view.selected_def = selected_def.min(view.results.len() - 1);
+            view.scroll_amount = scroll_amount;
+        }
+    }
+
+    fn show_view(&mut self, view: View) {
+        if !matches!(self.mode, AppMode::Define) {
+            self.set_mode(AppMode::Define);
+        }
+
+        self.searched.set_text(&view.searched);
+        self.searched.move_cursor(CursorDirection::Last);
+        self.suggestions.settle(&view.searched);
+        self.word = view.word;
+        self.results = view.results;
+        self.selected_def = view.selected_def;
+        self.selected_link = None;
+        self.scroll_amount = view.scroll_amount;
+    }
+
+    // Bookmarks
+    /// Keeps the definition shown in the current word list
+    pub fn bookmark(&mut self) {
+        if self.word.is_empty() {
+            self.notice = Some(String::from("No definition to bookmark"));
+            return;
+        }
+
+        let definition = self.results[self.selected_def].item.clone();
+        let list = self.word_list.clone();
+        self.notice = Some(match self.bookmarks.add(&list, self.word.clone(), definition) {
+            Ok(()) => format!("Bookmarked {} in {}", self.word, list),
+            Err(e) => format!("Could not bookmark {}: {}", self.word, e)
+        });
+    }
+
+    /// The list bookmarks go to
+    pub fn word_list(&self) -> &str {
+        &self.word_list
+    }
+
+    pub fn selected_list(&self) -> usize {
+        self.selected_list
+    }
+
+    pub fn selected_entry(&self) -> Option<&Bookmark> {
+        self.bookmarks.lists.get(self.selected_list)
+            .and_then(|list| list.entries.get(self.selected_bookmark))
+    }
+
+    pub fn selected_bookmark(&self) -> usize {
+        self.selected_bookmark
+    }
+
+    /// Bookmarks go to the list named in the search bar from now on
+    pub fn run_bookmarks(&mut self) {
+        let name = self.searched.text().trim().to_owned();
+        if name.is_empty() {
+            return;
+        }
+
+        if !Bookmarks::valid_name(&name) {
+            self.notice = Some(format!("Invalid list name: {}", name));
+            return;
+        }
+
+        self.selected_list = self.bookmarks.list(&name);
+        self.selected_bookmark = 0;
+        self.searched.clear();
+        self.notice = Some(format!("Bookmarks now go to {}", name));
+        self.word_list = name;
+    }
+
+    // Shows it as if just defined
+    fn open_bookmark(&mut self) {
+        let bookmark = match self.selected_entry() {
+            Some(bookmark) => bookmark,
+            None => return
+        };
+
+        let view = View {
+            searched: format!("{} @{}", bookmark.word, bookmark.definition.source.name),
+            word: bookmark.word.clone(),
+            results: vec![OnServer { server: self.current_server, item: bookmark.definition.clone() }],
+            selected_def: 0,
+            scroll_amount: 0
+        };
+
+        self.keep_view();
+        self.visited.visit(view.clone());
+        self.show_view(view);
+    }
+
+    pub fn delete_bookmark(&mut self) {
+        if self.focus != Focus::Bookmarks || self.selected_entry().is_none() {
+            return;
+        }
+
+        if let Err(e) = self.bookmarks.remove(self.selected_list, self.selected_bookmark) {
+            self.notice = Some(format!("Could not remove the bookmark: {}", e));
+        }
+
+        let len = self.bookmarks.lists[self.selected_list].entries.len();
+        self.selected_bookmark = self.selected_bookmark.min(len.saturating_sub(1));
+    }
+
+    /// Writes the selected list as CSV, and as notes to import in Anki,
+    /// in the export directory
+    pub fn export_bookmarks(&mut self) {
+        let list = match self.bookmarks.lists.get(self.selected_list) {
+            Some(list) => list,
+            None => return
+        };
+
+        let dir = match bookmarks::export_dir() {
+            Some(dir) => dir,
+            None => {
+                self.notice = Some(format!("Could not export {}: no data directory", list.name));
+                return;
+            }
+        };
+
+        let csv = dir.join(format!("{}.csv", list.name));
+        let anki = dir.join(format!("{}-anki.txt", list.name));
+        let written = fs::create_dir_all(&dir)
+            .and_then(|_| fs::write(&csv, list.to_csv()))
+            .and_then(|_| fs::write(&anki, list.to_anki()));
+
+        self.notice = Some(match written {
+            Ok(()) => format!("Exported {} to {} and {}", list.name, csv.display(), anki.display()),
+            Err(e) => format!("Could not export {}: {}", list.name, e)
+        });
+    }
+
+    // Quiz
+    /// Reviews the cards due, with one for each word bookmarked
+    fn start_review(&mut self) {
+        let words = self.bookmarks.lists.iter()
+            .flat_map(|list| list.entries.iter())
+            .map(|b| (b.word.as_str(), b.definition.source.name.as_str()));
+
+        let now = quiz::now();
+        self.deck.sync(words, now);
+        self.review = Some(Review::new(&self.deck, now));
+    }
+
+    pub fn review(&self) -> Option<&Review> {
+        self.review.as_ref()
+    }
+
+    /// The card to review
+    pub fn card(&self) -> Option<&Card> {
+        self.review.as_ref()?.current().map(|i| self.deck.card(i))
+    }
+
+    /// Looks the word of the card up
+    pub fn reveal(&mut self) {
+        let card = match self.card() {
+            Some(card) => card,
+            None => return
+        };
+        if self.review.as_ref().map_or(false, |review| review.revealed) {
+            return;
+        }
+
+        let (word, db) = (card.word.clone(), Database::from(card.database.clone()));
+        self.definition_reset();
+        self.searched.set_text(&format!("{} @{}", word, db.name));
+        self.searched.move_cursor(CursorDirection::Last);
+        self.define(vec![self.current_server], word, db);
+
+        if let Some(ref mut review) = self.review {
+            review.revealed = true;
+        }
+    }
+
+    /// Schedules the card once revealed, from how well it was recalled
+    pub fn grade(&mut self, quality: u8) {
+        let review = match self.review {
+            Some(ref mut review) if review.revealed => review,
+            _ => return
+        };
+
+        if let Err(e) = review.grade(&mut self.deck, quality, quiz::now()) {
+            self.notice = Some(format!("Could not save the quiz: {}", e));
+        }
         self.scroll_amount = 0;
     }
 
@@ -332,56 +1549,191 @@ impl App {
             self.search_reset();
         }
     }
-}
 
-struct History {
-    current: usize,
-    items: Vec<String>
-}
+    // Reverse search in the history
+    pub fn history_search(&self) -> Option<&HistorySearch> {
+        self.history_search.as_ref()
+    }
 
-pub enum HistoryMovement {
-    First,
-    Last,
-    Previous,
-    Next
-}
+    /// Starts searching the history, or looks for an older entry
+    pub fn search_history(&mut self) {
+        match self.history_search {
+            Some(ref mut search) => search.older(&self.history),
+            None => self.history_search = Some(HistorySearch::new(self.searched.text().to_owned()))
+        }
+        self.show_history_search();
+    }
 
-impl History {
-    pub fn new() -> Self {
-        Self {
-            current: 0,
-            items: vec![]
+    pub fn history_search_edit(&mut self, c: char) {
+        if let Some(ref mut search) = self.history_search {
+            search.query.push(c);
+            search.update(&self.history);
         }
+        self.show_history_search();
     }
 
-    pub fn push(&mut self, item: String) {
-        if self.items.len() > 0 {
-            self.items.truncate(self.current + 1);
+    pub fn history_search_delete(&mut self) {
+        if let Some(ref mut search) = self.history_search {
+            search.query.pop();
+            search.update(&self.history);
         }
+        self.show_history_search();
+    }
 
-        self.current = self.items.len();
+    /// Leaves the entry found in the search bar
+    pub fn accept_history_search(&mut self) {
+        self.history_search = None;
+        self.suggestions.settle(self.searched.text());
+    }
 
-        self.items.push(item);
+    pub fn cancel_history_search(&mut self) {
+        if let Some(search) = self.history_search.take() {
+            self.searched.set_text(&search.original);
+            self.searched.move_cursor(CursorDirection::Last);
+            self.suggestions.settle(self.searched.text());
+        }
     }
 
-    pub fn goto(&mut self, m: HistoryMovement) {
-        match m {
-            HistoryMovement::First => { self.current = 0 },
-            HistoryMovement::Last => { self.current = self.items.len() - 1 },
-            HistoryMovement::Next => {
-                if self.current + 1 < self.items.len() {
-                    self.current += 1;
-                }
-            },
-            HistoryMovement::Previous => {
-                if self.current > 0 {
-                    self.current -= 1;
-                }
+    fn show_history_search(&mut self) {
+        let found = self.history_search.as_ref()
+            .and_then(|search| search.found)
+            .and_then(|i| self.history.get(i))
+            .map(|entry| entry.text.clone());
+
+        if let Some(text) = found {
+            self.searched.set_text(&text);
+            self.searched.move_cursor(CursorDirection::Last);
+        }
+    }
+
+    // Search in the text shown
+    pub fn find(&self) -> Option<&str> {
+        self.find.as_deref()
+    }
+
+    pub fn start_find(&mut self) {
+        self.find = Some(String::new());
+    }
+
+    pub fn find_edit(&mut self, c: char) {
+        if let Some(ref mut query) = self.find {
+            query.push(c);
+        }
+    }
+
+    /// Deletes the last character, or stops when there is none
+    pub fn find_delete(&mut self) {
+        if let Some(ref mut query) = self.find {
+            if query.pop().is_none() {
+                self.find = None;
+            }
+        }
+    }
+
+    pub fn cancel_find(&mut self) {
+        self.find = None;
+    }
+
+    /// Goes to the next line with the query, or with the previous query
+    /// when it is empty
+    pub fn accept_find(&mut self) {
+        let query = match self.find.take() {
+            Some(query) if !query.is_empty() => query,
+            _ => match self.found {
+                Some(ref found) => found.clone(),
+                None => return
             }
+        };
+
+        let from = match self.mode {
+            AppMode::Match => self.selected_match,
+            _ => self.scroll_amount as usize
+        };
+        self.notice = None;
+        match find::next_line(&self.text(), &query, from) {
+            Some(line) if self.mode == AppMode::Match => self.selected_match = line,
+            Some(line) => self.scroll_amount = line as u16,
+            None => self.notice = Some(format!("Pattern not found: {}", query))
+        }
+        self.found = Some(query);
+    }
+
+    // The lines shown, as far as they can be scrolled
+    fn text(&self) -> Vec<String> {
+        match self.mode {
+            AppMode::Define | AppMode::Quiz => self.results[self.selected_def].text.clone(),
+            AppMode::Match => self.matches.iter().map(|m| format!("{} ({})", m.word, m.source.name)).collect(),
+            AppMode::Info => self.server().db_info.clone(),
+            AppMode::Command => self.commands.iter()
+                .flat_map(|exchange| {
+                    let answers = exchange.answers.iter().flat_map(|answer| match answer {
+                        RawAnswer::Reply(reply) => vec![reply.to_string()],
+                        RawAnswer::Text(text) => text.clone()
+                    });
+                    std::iter::once(format!("> {}", exchange.line)).chain(answers)
+                })
+                .collect(),
+            AppMode::Bookmarks => self.selected_entry().map_or_else(Vec::new, |b| b.definition.text.clone())
         }
     }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use dictproto::testing::{MockHandle, MockServer};
+    use std::sync::mpsc;
+
+    fn definition(text: &str) -> Definition {
+        Definition {
+            source: Database { name: String::from("wn"), desc: String::from("WordNet") },
+            text: vec![text.to_owned()]
+        }
+    }
+
+    fn app(server: &MockHandle, words: &[&str]) -> App {
+        let config = Config { servers: vec![format!("dict://{}", server.addr())], ..Config::default() };
+        let mut bookmarks = Bookmarks::new();
+        for word in words {
+            bookmarks.add(DEFAULT_LIST, word.to_string(), definition("a knot")).unwrap();
+        }
+
+        let (events, _) = mpsc::channel();
+        App::new(config, Vec::new(), History::new(10), bookmarks, Deck::new(), Cache::new(10, None), events)
+    }
+
+    #[test]
+    fn reveal_from_another_tab() {
+        let server = MockServer::new().start().unwrap();
+        let mut app = app(&server, &["tie"]);
+        app.results = (0..3).map(|i| OnServer { server: 0, item: definition(&i.to_string()) }).collect();
+        app.selected_def = 2;
+        app.scroll_amount = 1;
+
+        app.set_mode(AppMode::Quiz);
+        app.reveal();
+        assert_eq!(app.text_height(), 1);
+        assert!(app.links().is_empty());
+
+        // The answer is not a page to go back to
+        app.found_defs.push(OnServer { server: 0, item: definition("a knot") });
+        app.running.as_mut().unwrap().servers.clear();
+        app.finish();
+        assert_eq!(app.results.len(), 1);
+        assert!(app.visited.current_mut().is_none());
+    }
+
+    #[test]
+    fn quiz_over_saved_words() {
+        let server = MockServer::new().start().unwrap();
+        let mut app = app(&server, &["tie", "tin"]);
+
+        app.set_mode(AppMode::Quiz);
+        assert_eq!(app.review().unwrap().left(), 2);
 
-    pub fn current(&self) -> Option<&String> {
-        self.items.get(self.current)
+        app.bookmarks.remove(0, 0).unwrap();
+        app.set_mode(AppMode::Quiz);
+        assert_eq!(app.review().unwrap().left(), 1);
+        assert_eq!(app.card().unwrap().word, "tin");
     }
 }
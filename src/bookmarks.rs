@@ -0,0 +1,255 @@
+use crate::xdg;
+
+use dictproto::{Database, Definition};
+use dictproto::escape::{escape, unescape};
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_LIST: &str = "words";
+
+/// $XDG_DATA_HOME/redict/bookmarks, with one file per list
+pub fn default_dir() -> Option<PathBuf> {
+    xdg::data_dir().map(|dir| dir.join("bookmarks"))
+}
+
+/// $XDG_DATA_HOME/redict/exports, where lists are exported
+pub fn export_dir() -> Option<PathBuf> {
+    xdg::data_dir().map(|dir| dir.join("exports"))
+}
+
+/// A definition kept for later
+pub struct Bookmark {
+    pub word: String,
+    pub definition: Definition,
+    /// Seconds since the epoch
+    pub time: u64
+}
+
+/// Words kept under a name
+pub struct WordList {
+    pub name: String,
+    pub entries: Vec<Bookmark>
+}
+
+// RFC 4180
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+fn html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('\t', " ")
+}
+
+impl WordList {
+    // Fields are separated by tabs, definitions by lines
+    fn parse(name: String, content: &str) -> Self {
+        let entries = content.lines().filter_map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            match fields[..] {
+                [time, word, db, desc, text] => Some(Bookmark {
+                    word: unescape(word),
+                    definition: Definition {
+                        source: Database { name: unescape(db), desc: unescape(desc) },
+                        text: unescape(text).split('\n').map(String::from).collect()
+                    },
+                    time: time.parse().ok()?
+                }),
+                _ => None
+            }
+        }).collect();
+
+        WordList { name, entries }
+    }
+
+    fn content(&self) -> String {
+        self.entries.iter()
+            .map(|b| {
+                let source = &b.definition.source;
+                format!("{}\t{}\t{}\t{}\t{}\n", b.time, escape(&b.word), escape(&source.name),
+                    escape(&source.desc), escape(&b.definition.text.join("\n")))
+            })
+            .collect()
+    }
+
+    /// With a header, and a line per word: the word, the database and the
+    /// definition
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("word,database,definition\r\n");
+
+        for b in &self.entries {
+            csv.push_str(&format!("{},{},{}\r\n", csv_field(&b.word),
+                csv_field(&b.definition.source.name), csv_field(&b.definition.text.join("\n"))));
+        }
+
+        csv
+    }
+
+    /// Notes for Anki, with the word on the front, and on the back the
+    /// definition and its database
+    pub fn to_anki(&self) -> String {
+        let mut tsv = String::from("#separator:tab\n#html:true\n");
+
+        for b in &self.entries {
+            let text: Vec<String> = b.definition.text.iter().map(|line| html(line)).collect();
+            tsv.push_str(&format!("{}\t{}<br><br>{}\n", html(&b.word), text.join("<br>"),
+                html(&b.definition.source.desc)));
+        }
+
+        tsv
+    }
+}
+
+/// The word lists, saved to a directory after each change
+pub struct Bookmarks {
+    pub lists: Vec<WordList>,
+    dir: Option<PathBuf>
+}
+
+impl Bookmarks {
+    pub fn new() -> Self {
+        Bookmarks {
+            lists: Vec::new(),
+            dir: None
+        }
+    }
+
+    /// The lists saved in dir, if any
+    pub fn load(dir: PathBuf) -> Self {
+        let mut lists = Vec::new();
+
+        if let Ok(files) = fs::read_dir(&dir) {
+            for file in files.flatten() {
+                let path = file.path();
+                let name = match path.file_stem().and_then(|s| s.to_str()) {
+                    Some(name) if path.extension().map_or(false, |e| e == "tsv") => name.to_owned(),
+                    _ => continue
+                };
+
+                if let Ok(content) = fs::read_to_string(&path) {
+                    lists.push(WordList::parse(name, &content));
+                }
+            }
+        }
+        lists.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Bookmarks { lists, dir: Some(dir) }
+    }
+
+    /// Names are those of files
+    pub fn valid_name(name: &str) -> bool {
+        !name.is_empty() && !name.starts_with('.') && !name.contains(&['/', '\\', '\t'][..])
+    }
+
+    /// Index of the list, created when missing
+    pub fn list(&mut self, name: &str) -> usize {
+        match self.lists.iter().position(|list| list.name == name) {
+            Some(i) => i,
+            None => {
+                self.lists.push(WordList { name: name.to_owned(), entries: Vec::new() });
+                self.lists.len() - 1
+            }
+        }
+    }
+
+    /// Keeps the definition of word in the list, once
+    pub fn add(&mut self, list: &str, word: String, definition: Definition) -> io::Result<()> {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        let i = self.list(list);
+        let entries = &mut self.lists[i].entries;
+        entries.retain(|b| b.word != word || b.definition.source.name != definition.source.name);
+        entries.push(Bookmark { word, definition, time });
+
+        self.save(i)
+    }
+
+    pub fn remove(&mut self, list: usize, entry: usize) -> io::Result<()> {
+        self.lists[list].entries.remove(entry);
+        self.save(list)
+    }
+
+    fn save(&self, list: usize) -> io::Result<()> {
+        let dir = match self.dir {
+            Some(ref dir) => dir,
+            None => return Ok(())
+        };
+
+        let list = &self.lists[list];
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join(format!("{}.tsv", list.name)), list.content())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+
+    fn definition(db: &str, text: &[&str]) -> Definition {
+        Definition {
+            source: Database { name: db.to_owned(), desc: String::from("WordNet") },
+            text: text.iter().map(|line| line.to_string()).collect()
+        }
+    }
+
+    #[test]
+    fn add_once() {
+        let mut bookmarks = Bookmarks::new();
+        bookmarks.add("words", String::from("tie"), definition("wn", &["tie"])).unwrap();
+        bookmarks.add("words", String::from("tie"), definition("gcide", &["tie"])).unwrap();
+        bookmarks.add("words", String::from("tie"), definition("wn", &["a tie"])).unwrap();
+
+        let entries = &bookmarks.lists[0].entries;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].definition.text, vec!["a tie"]);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let dir = env::temp_dir().join(format!("redict-bookmarks-{}", std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+
+        let mut bookmarks = Bookmarks::load(dir.clone());
+        bookmarks.add("verbs", String::from("tie"), definition("wn", &["to fasten", "\tby a knot"])).unwrap();
+        bookmarks.add("nouns", String::from("tin"), definition("wn", &["a metal"])).unwrap();
+
+        let bookmarks = Bookmarks::load(dir.clone());
+        fs::remove_dir_all(&dir).ok();
+
+        let names: Vec<&str> = bookmarks.lists.iter().map(|list| list.name.as_str()).collect();
+        assert_eq!(names, vec!["nouns", "verbs"]);
+
+        let tie = &bookmarks.lists[1].entries[0];
+        assert_eq!(tie.word, "tie");
+        assert_eq!(tie.definition.source.name, "wn");
+        assert_eq!(tie.definition.source.desc, "WordNet");
+        assert_eq!(tie.definition.text, vec!["to fasten", "\tby a knot"]);
+    }
+
+    #[test]
+    fn export() {
+        let mut bookmarks = Bookmarks::new();
+        bookmarks.add("words", String::from("tie"), definition("wn", &["to fasten, \"knot\"", "<v>"])).unwrap();
+        let list = &bookmarks.lists[0];
+
+        assert_eq!(list.to_csv(), "word,database,definition\r\ntie,wn,\"to fasten, \"\"knot\"\"\n<v>\"\r\n");
+        assert_eq!(list.to_anki(), "#separator:tab\n#html:true\ntie\tto fasten, \"knot\"<br>&lt;v&gt;<br><br>WordNet\n");
+    }
+
+    #[test]
+    fn names() {
+        assert!(Bookmarks::valid_name("english verbs"));
+        assert!(!Bookmarks::valid_name("../verbs"));
+        assert!(!Bookmarks::valid_name(".hidden"));
+        assert!(!Bookmarks::valid_name(""));
+    }
+}
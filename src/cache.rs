@@ -0,0 +1,261 @@
+use crate::xdg;
+
+use dictproto::{Database, Definition};
+use dictproto::escape::{escape, unescape};
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Definitions kept in memory
+pub const MEMORY_SIZE: usize = 100;
+
+/// $XDG_CACHE_HOME/redict/definitions
+pub fn default_dir() -> Option<PathBuf> {
+    xdg::cache_dir().map(|dir| dir.join("definitions"))
+}
+
+/// What was looked up, and where
+#[derive(Debug, Clone, PartialEq)]
+pub struct Key {
+    pub server: String,
+    pub database: String,
+    pub word: String
+}
+
+struct Entry {
+    key: Key,
+    /// Seconds since the epoch
+    time: u64,
+    definitions: Vec<Definition>
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Longer file names are cut, and told apart by a hash of the whole name
+const MAX_FILE_NAME: usize = 200;
+
+// Any name makes a file name, not hidden
+fn file_name(name: &str) -> String {
+    let mut file = String::with_capacity(name.len());
+
+    for (i, b) in name.bytes().enumerate() {
+        if b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || (b == b'.' && i > 0) {
+            file.push(b as char);
+        } else {
+            file.push_str(&format!("%{:02X}", b));
+        }
+    }
+
+    if file.len() > MAX_FILE_NAME {
+        let hash = format!("-{:x}", md5::compute(name));
+        file.truncate(MAX_FILE_NAME - hash.len());
+        file.push_str(&hash);
+    }
+
+    file
+}
+
+/// The definitions found, the latest ones in memory, all of them in a
+/// directory with a file per word
+pub struct Cache {
+    /// The most recently used last
+    memory: Vec<Entry>,
+    capacity: usize,
+    /// In seconds, entries never expire without it
+    max_age: Option<u64>,
+    dir: Option<PathBuf>,
+    offline: bool
+}
+
+impl Cache {
+    pub fn new(capacity: usize, max_age: Option<u64>) -> Self {
+        Cache {
+            memory: Vec::new(),
+            capacity,
+            max_age,
+            dir: None,
+            offline: false
+        }
+    }
+
+    /// A cache kept in dir
+    pub fn open(dir: PathBuf, capacity: usize, max_age: Option<u64>) -> Self {
+        let mut cache = Self::new(capacity, max_age);
+        cache.dir = Some(dir);
+        cache
+    }
+
+    /// Nothing is looked up on the servers anymore, and nothing expires
+    pub fn offline(mut self) -> Self {
+        self.offline = true;
+        self.max_age = None;
+        self
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    fn path(&self, key: &Key) -> Option<PathBuf> {
+        let dir = self.dir.as_ref()?;
+        Some(dir.join(file_name(&key.server)).join(file_name(&key.database)).join(file_name(&key.word)))
+    }
+
+    fn fresh(&self, entry: &Entry) -> bool {
+        match self.max_age {
+            Some(age) => now() < entry.time.saturating_add(age),
+            None => true
+        }
+    }
+
+    fn remember(&mut self, entry: Entry) {
+        self.memory.retain(|e| e.key != entry.key);
+        self.memory.push(entry);
+
+        let len = self.memory.len();
+        self.memory.drain(..len.saturating_sub(self.capacity));
+    }
+
+    // The first line is the time, the others the definitions: their
+    // database, its description and the text, separated by tabs
+    fn read(&self, key: &Key) -> Option<Entry> {
+        let content = fs::read_to_string(self.path(key)?).ok()?;
+        let mut lines = content.lines();
+
+        let time = lines.next()?.parse().ok()?;
+        let definitions = lines.map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            match fields[..] {
+                [db, desc, text] => Some(Definition {
+                    source: Database { name: unescape(db), desc: unescape(desc) },
+                    text: unescape(text).split('\n').map(String::from).collect()
+                }),
+                _ => None
+            }
+        }).collect::<Option<Vec<Definition>>>()?;
+
+        Some(Entry { key: key.clone(), time, definitions })
+    }
+
+    fn write(&self, entry: &Entry) -> io::Result<()> {
+        let path = match self.path(&entry.key) {
+            Some(path) => path,
+            None => return Ok(())
+        };
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut content = format!("{}\n", entry.time);
+        for def in &entry.definitions {
+            content.push_str(&format!("{}\t{}\t{}\n", escape(&def.source.name), escape(&def.source.desc),
+                escape(&def.text.join("\n"))));
+        }
+        fs::write(path, content)
+    }
+
+    /// The definitions found for key, unless they expired
+    pub fn get(&mut self, key: &Key) -> Option<Vec<Definition>> {
+        let entry = match self.memory.iter().position(|e| e.key == *key) {
+            Some(i) => self.memory.remove(i),
+            None => self.read(key)?
+        };
+
+        if !self.fresh(&entry) {
+            if let Some(path) = self.path(key) {
+                fs::remove_file(path).ok();
+            }
+            return None;
+        }
+
+        let definitions = entry.definitions.clone();
+        self.remember(entry);
+
+        Some(definitions)
+    }
+
+    /// Keeps the definitions found for key. They are saved, as far as
+    /// possible.
+    pub fn insert(&mut self, key: Key, definitions: Vec<Definition>) {
+        let entry = Entry { key, time: now(), definitions };
+
+        self.write(&entry).ok();
+        self.remember(entry);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+
+    fn key(word: &str) -> Key {
+        Key { server: String::from("dict.org:2628"), database: String::from("*"), word: word.to_owned() }
+    }
+
+    fn definitions(text: &[&str]) -> Vec<Definition> {
+        vec![Definition {
+            source: Database { name: String::from("wn"), desc: String::from("WordNet") },
+            text: text.iter().map(|line| line.to_string()).collect()
+        }]
+    }
+
+    #[test]
+    fn file_names() {
+        assert_eq!(file_name("dict.org:2628"), "dict.org%3A2628");
+        assert_eq!(file_name("../a b"), "%2E.%2Fa%20b");
+
+        let long = file_name(&"é".repeat(100));
+        assert_eq!(long.len(), MAX_FILE_NAME);
+        assert_ne!(long, file_name(&"é".repeat(101)));
+    }
+
+    #[test]
+    fn least_recently_used() {
+        let mut cache = Cache::new(2, None);
+        cache.insert(key("tie"), definitions(&["tie"]));
+        cache.insert(key("tin"), definitions(&["tin"]));
+        assert!(cache.get(&key("tie")).is_some());
+
+        // tin was used the longest ago
+        cache.insert(key("tip"), definitions(&["tip"]));
+        assert!(cache.get(&key("tin")).is_none());
+        assert!(cache.get(&key("tie")).is_some());
+    }
+
+    #[test]
+    fn expiry() {
+        let mut cache = Cache::new(2, Some(0));
+        cache.insert(key("tie"), definitions(&["tie"]));
+
+        assert!(cache.get(&key("tie")).is_none());
+    }
+
+    #[test]
+    fn save_and_load() {
+        let dir = env::temp_dir().join(format!("redict-cache-{}", std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+
+        let mut cache = Cache::open(dir.clone(), 1, Some(60));
+        cache.insert(key("tie"), definitions(&["to fasten", "\tby a knot"]));
+        cache.insert(key("tin"), definitions(&["a metal"]));
+
+        // From the files
+        let mut cache = Cache::open(dir.clone(), 1, Some(60));
+        let tie = cache.get(&key("tie"));
+        let other = cache.get(&Key { server: String::from("localhost:2628"), ..key("tin") });
+        fs::remove_dir_all(&dir).ok();
+
+        let tie = tie.unwrap();
+        assert_eq!(tie[0].source.desc, "WordNet");
+        assert_eq!(tie[0].text, vec!["to fasten", "\tby a knot"]);
+        assert!(other.is_none());
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use unicode_segmentation::UnicodeSegmentation;
+
+// Several databases often know the same word
+fn dedup(words: &mut Vec<String>) {
+    let mut seen = HashSet::new();
+    words.retain(|w| seen.insert(w.clone()));
+}
+
+fn cycle(selected: Option<usize>, len: usize, forward: bool) -> usize {
+    match (selected, forward) {
+        (None, true) => 0,
+        (None, false) => len - 1,
+        (Some(i), true) => (i + 1) % len,
+        (Some(i), false) => (i + len - 1) % len
+    }
+}
+
+/// Candidates for the word before the cursor in the search bar
+pub struct Completion {
+    /// Grapheme position of the completed word in the search bar
+    pub start: usize,
+    end: usize,
+    /// Id of the background query, while its candidates are still to come
+    pub query: Option<usize>,
+    pub candidates: Vec<String>,
+    pub selected: Option<usize>
+}
+
+impl Completion {
+    pub fn new(start: usize, end: usize, candidates: Vec<String>) -> Self {
+        Completion {
+            start,
+            end,
+            query: None,
+            candidates,
+            selected: None
+        }
+    }
+
+    pub fn pending(start: usize, end: usize, query: usize) -> Self {
+        Completion {
+            query: Some(query),
+            ..Self::new(start, end, Vec::new())
+        }
+    }
+
+    /// The names that start with prefix, behind sigil
+    pub fn filter<'a, I>(sigil: &str, prefix: &str, names: I) -> Vec<String>
+    where I: Iterator<Item = &'a str> {
+        names.filter(|name| name.starts_with(prefix))
+            .map(|name| format!("{}{}", sigil, name))
+            .collect()
+    }
+
+    pub fn found(&mut self, mut words: Vec<String>) {
+        dedup(&mut words);
+        self.candidates = words;
+        self.query = None;
+    }
+
+    /// Selects the next candidate, or the previous one, and returns it
+    /// with the range it replaces.
+    pub fn cycle(&mut self, forward: bool) -> Option<(usize, usize, &str)> {
+        let len = self.candidates.len();
+        if len == 0 {
+            return None;
+        }
+
+        let selected = cycle(self.selected, len, forward);
+        self.selected = Some(selected);
+
+        let candidate = &self.candidates[selected];
+        let end = self.end;
+        self.end = self.start + candidate.graphemes(true).count();
+
+        Some((self.start, end, candidate))
+    }
+}
+
+/// Words suggested while typing, once the search bar stays still
+pub struct Suggestions {
+    pub words: Vec<String>,
+    pub selected: Option<usize>,
+    debounce: Duration,
+    // Search bar text they are computed for
+    text: String,
+    edited: Option<Instant>,
+    query: usize
+}
+
+impl Default for Suggestions {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(300))
+    }
+}
+
+impl Suggestions {
+    pub fn new(debounce: Duration) -> Self {
+        Suggestions {
+            words: Vec::new(),
+            selected: None,
+            debounce,
+            text: String::new(),
+            edited: None,
+            query: 0
+        }
+    }
+
+    /// Notes what the search bar contains. Returns true when it did not
+    /// change for a while, and suggestions should be looked up.
+    pub fn update(&mut self, text: &str) -> bool {
+        if text != self.text {
+            self.text = text.to_owned();
+            self.edited = Some(Instant::now());
+            self.close();
+            return false;
+        }
+
+        match self.edited {
+            Some(at) if at.elapsed() >= self.debounce => {
+                self.edited = None;
+                true
+            },
+            _ => false
+        }
+    }
+
+    /// How long until update has to be called again
+    pub fn timeout(&self) -> Option<Duration> {
+        self.edited.map(|at| self.debounce.checked_sub(at.elapsed()).unwrap_or_default())
+    }
+
+    /// Nothing is suggested for text, until it changes
+    pub fn settle(&mut self, text: &str) {
+        self.text = text.to_owned();
+        self.edited = None;
+        self.close();
+    }
+
+    pub fn close(&mut self) {
+        self.words.clear();
+        self.selected = None;
+        // Answers to pending queries are outdated
+        self.query += 1;
+    }
+
+    /// Id of a new lookup
+    pub fn next_query(&mut self) -> usize {
+        self.query += 1;
+        self.query
+    }
+
+    pub fn found(&mut self, query: usize, mut words: Vec<String>) {
+        if query == self.query {
+            dedup(&mut words);
+            self.words = words;
+            self.selected = None;
+        }
+    }
+
+    pub fn select(&mut self, forward: bool) {
+        if !self.words.is_empty() {
+            self.selected = Some(cycle(self.selected, self.words.len(), forward));
+        }
+    }
+
+    pub fn selected(&self) -> Option<&str> {
+        self.selected.map(|i| self.words[i].as_str())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn filter() {
+        let names = vec!["fd-eng-fra", "fd-fra-eng", "wn"];
+        let found = Completion::filter("@", "fd-", names.into_iter());
+
+        assert_eq!(found, vec!["@fd-eng-fra", "@fd-fra-eng"]);
+    }
+
+    #[test]
+    fn cycle() {
+        let mut completion = Completion::new(3, 6, vec![String::from("@fd-eng-fra"), String::from("@wn")]);
+
+        assert_eq!(completion.cycle(true), Some((3, 6, "@fd-eng-fra")));
+        assert_eq!(completion.cycle(true), Some((3, 14, "@wn")));
+        assert_eq!(completion.cycle(true), Some((3, 6, "@fd-eng-fra")));
+        assert_eq!(completion.cycle(false), Some((3, 14, "@wn")));
+    }
+
+    #[test]
+    fn nothing_found() {
+        let mut completion = Completion::pending(0, 2, 1);
+        completion.found(vec![]);
+
+        assert_eq!(completion.cycle(true), None);
+    }
+
+    #[test]
+    fn debounce() {
+        let mut suggestions = Suggestions::new(Duration::from_millis(0));
+
+        assert!(!suggestions.update("ti"));
+        assert!(suggestions.update("ti"));
+        // Only once
+        assert!(!suggestions.update("ti"));
+        assert_eq!(suggestions.timeout(), None);
+    }
+
+    #[test]
+    fn outdated_suggestions() {
+        let mut suggestions = Suggestions::new(Duration::from_millis(0));
+        suggestions.update("ti");
+        suggestions.update("ti");
+        let query = suggestions.next_query();
+
+        suggestions.update("tin");
+        suggestions.found(query, vec![String::from("tie")]);
+        assert!(suggestions.words.is_empty());
+    }
+
+    #[test]
+    fn select_suggestion() {
+        let mut suggestions = Suggestions::new(Duration::from_millis(0));
+        let query = suggestions.next_query();
+        suggestions.found(query, vec![String::from("tie"), String::from("tin")]);
+        assert_eq!(suggestions.selected(), None);
+
+        suggestions.select(false);
+        assert_eq!(suggestions.selected(), Some("tin"));
+        suggestions.select(true);
+        assert_eq!(suggestions.selected(), Some("tie"));
+
+        suggestions.settle("tie");
+        assert_eq!(suggestions.selected(), None);
+        assert!(!suggestions.update("tie"));
+    }
+
+    #[test]
+    fn found_once() {
+        let mut completion = Completion::pending(0, 2, 1);
+        completion.found(vec![String::from("tie"), String::from("tie"), String::from("tin")]);
+
+        assert_eq!(completion.candidates, vec!["tie", "tin"]);
+        assert_eq!(completion.query, None);
+    }
+}
@@ -0,0 +1,193 @@
+use crate::{cache, history, xdg};
+
+use dictproto::connection::Timeouts;
+use serde::Deserialize;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// $XDG_CONFIG_HOME/redict/config.toml
+pub fn default_path() -> Option<PathBuf> {
+    xdg::config_dir().map(|dir| dir.join("config.toml"))
+}
+
+/// What can be set in the configuration file. Missing settings keep
+/// their default.
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// Urls of the servers to connect to, each followed by its mirrors
+    /// after commas
+    pub servers: Vec<String>,
+    /// Searched when the search bar has no `@database`
+    pub database: String,
+    /// Used when the search bar has no `:strategy`
+    pub strategy: String,
+    /// The name sent with `CLIENT`
+    pub client: String,
+    /// Lines scrolled at once
+    pub scroll: u16,
+    pub history_size: usize,
+    /// Moves with the keys of vi, in its normal mode
+    pub vi: bool,
+    pub timeouts: TimeoutsConfig,
+    pub cache: CacheConfig,
+    /// Keys bound to actions, for all the modes or for each
+    pub keys: BTreeMap<String, BTreeMap<String, String>>
+}
+
+/// In seconds, 0 to wait forever
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimeoutsConfig {
+    pub connect: u64,
+    pub read: u64,
+    pub write: u64,
+    /// For a command to be sent and completely answered
+    pub command: u64
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// Definitions kept in memory
+    pub size: usize,
+    /// How long definitions stay in the cache
+    pub days: u64
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            servers: Vec::new(),
+            database: String::from("*"),
+            strategy: String::from("."),
+            client: String::from("redict"),
+            scroll: 10,
+            history_size: history::DEFAULT_SIZE,
+            vi: false,
+            timeouts: TimeoutsConfig::default(),
+            cache: CacheConfig::default(),
+            keys: BTreeMap::new()
+        }
+    }
+}
+
+impl Default for TimeoutsConfig {
+    fn default() -> Self {
+        TimeoutsConfig {
+            connect: 10,
+            read: 10,
+            write: 10,
+            command: 30
+        }
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            size: cache::MEMORY_SIZE,
+            days: 7
+        }
+    }
+}
+
+fn seconds(secs: u64) -> Option<Duration> {
+    if secs > 0 {
+        Some(Duration::from_secs(secs))
+    } else {
+        None
+    }
+}
+
+impl TimeoutsConfig {
+    pub fn timeouts(&self) -> Timeouts {
+        Timeouts {
+            connect: seconds(self.connect),
+            read: seconds(self.read),
+            write: seconds(self.write),
+            command: seconds(self.command)
+        }
+    }
+}
+
+impl Config {
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let config: Config = toml::from_str(content).map_err(|e| e.to_string())?;
+
+        if config.scroll == 0 {
+            return Err(String::from("scroll must be at least 1"));
+        }
+        Ok(config)
+    }
+
+    /// The configuration in path, the default one when there is no such
+    /// file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(content) => Self::parse(&content),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn empty_is_default() {
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn parse_config() {
+        let config = Config::parse(r#"
+            servers = ["dict://dict.org,dict://mirror.example"]
+            database = "wn"
+            scroll = 5
+            history-size = 10
+            vi = true
+
+            [timeouts]
+            command = 0
+
+            [cache]
+            days = 30
+        "#).unwrap();
+
+        assert_eq!(config.servers, vec!["dict://dict.org,dict://mirror.example"]);
+        assert_eq!(config.database, "wn");
+        assert_eq!(config.strategy, ".");
+        assert_eq!(config.scroll, 5);
+        assert_eq!(config.history_size, 10);
+        assert!(config.vi);
+        assert_eq!(config.cache, CacheConfig { size: 100, days: 30 });
+
+        let timeouts = config.timeouts.timeouts();
+        assert_eq!(timeouts.connect, Some(Duration::from_secs(10)));
+        assert_eq!(timeouts.command, None);
+    }
+
+    #[test]
+    fn unknown_setting() {
+        assert!(Config::parse("scrol = 5").is_err());
+        assert!(Config::parse("scroll = \"5\"").is_err());
+    }
+
+    #[test]
+    fn no_scroll() {
+        assert!(Config::parse("scroll = 0").is_err());
+        assert_eq!(Config::parse("scroll = 65535").unwrap().scroll, u16::MAX);
+    }
+
+    #[test]
+    fn missing_file() {
+        assert_eq!(Config::load(Path::new("/nonexistent/config.toml")), Ok(Config::default()));
+    }
+}
@@ -0,0 +1,87 @@
+use crate::worker::Outcome;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+use termion::event::{Event as TermEvent, Key};
+use termion::input::TermRead;
+
+pub enum Event {
+    Key(Key),
+    /// An arrow with Alt held, which termion does not decode
+    Alt(Key),
+    /// What a command gave, for server and request ids
+    Answer(usize, usize, Outcome),
+    /// The request is complete on that server
+    Done(usize, usize),
+    /// Words completing the search bar, for the query of that id
+    Completions(usize, Vec<String>),
+    /// Words close to the one searched, for the query of that id
+    Suggestions(usize, Vec<String>),
+    /// Nothing happened for a while
+    Tick,
+    // Nothing more to read from the terminal
+    End
+}
+
+// Alt-Left is sent as ESC [ 1 ; 3 D, and so on
+fn alt_arrow(bytes: &[u8]) -> Option<Key> {
+    match bytes {
+        b"\x1b[1;3A" => Some(Key::Up),
+        b"\x1b[1;3B" => Some(Key::Down),
+        b"\x1b[1;3C" => Some(Key::Right),
+        b"\x1b[1;3D" => Some(Key::Left),
+        _ => None
+    }
+}
+
+/// Everything the main loop reacts to, keys as well as answers computed on
+/// other threads
+pub struct Events {
+    rx: Receiver<Event>,
+    tx: Sender<Event>
+}
+
+impl Events {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel();
+
+        let keys = tx.clone();
+        thread::spawn(move || {
+            for event in std::io::stdin().events() {
+                let event = match event {
+                    Ok(TermEvent::Key(key)) => Event::Key(key),
+                    Ok(TermEvent::Unsupported(bytes)) => match alt_arrow(&bytes) {
+                        Some(key) => Event::Alt(key),
+                        None => continue
+                    },
+                    Ok(_) => continue,
+                    Err(_) => break
+                };
+
+                if keys.send(event).is_err() {
+                    return;
+                }
+            }
+            keys.send(Event::End).ok();
+        });
+
+        Events { rx, tx }
+    }
+
+    /// To send events from other threads
+    pub fn sender(&self) -> Sender<Event> {
+        self.tx.clone()
+    }
+
+    /// Waits for the next event, or until timeout
+    pub fn next(&self, timeout: Option<Duration>) -> Option<Event> {
+        match timeout {
+            Some(timeout) => match self.rx.recv_timeout(timeout) {
+                Ok(evt) => Some(evt),
+                Err(RecvTimeoutError::Timeout) => Some(Event::Tick),
+                Err(RecvTimeoutError::Disconnected) => None
+            },
+            None => self.rx.recv().ok()
+        }
+    }
+}
@@ -0,0 +1,49 @@
+/// Whether line has pattern. The case is ignored unless the pattern has
+/// capitals.
+fn contains(line: &str, pattern: &str) -> bool {
+    if pattern.chars().any(char::is_uppercase) {
+        line.contains(pattern)
+    } else {
+        line.to_lowercase().contains(pattern)
+    }
+}
+
+/// The first line after the one at from with pattern, going around from
+/// the start after the last line
+pub fn next_line(lines: &[String], pattern: &str, from: usize) -> Option<usize> {
+    let len = lines.len();
+
+    (1..=len).map(|i| (from + i) % len)
+        .find(|&i| contains(&lines[i], pattern))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lines(text: &[&str]) -> Vec<String> {
+        text.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn next() {
+        let text = lines(&["tie", "  to fasten", "  by a knot", "  a fastener"]);
+
+        assert_eq!(next_line(&text, "fast", 0), Some(1));
+        assert_eq!(next_line(&text, "fast", 1), Some(3));
+        // Around
+        assert_eq!(next_line(&text, "fast", 3), Some(1));
+        assert_eq!(next_line(&text, "tie", 0), Some(0));
+        assert_eq!(next_line(&text, "rope", 0), None);
+        assert_eq!(next_line(&[], "tie", 0), None);
+    }
+
+    #[test]
+    fn smart_case() {
+        let text = lines(&["Tie", "tie"]);
+
+        assert_eq!(next_line(&text, "tie", 1), Some(0));
+        assert_eq!(next_line(&text, "Tie", 0), Some(0));
+        assert_eq!(next_line(&text, "TIE", 0), None);
+    }
+}
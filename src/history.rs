@@ -0,0 +1,237 @@
+use crate::xdg;
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_SIZE: usize = 1000;
+
+/// $XDG_DATA_HOME/redict/history
+pub fn default_path() -> Option<PathBuf> {
+    xdg::data_dir().map(|dir| dir.join("history"))
+}
+
+pub struct Entry {
+    /// Seconds since the epoch
+    pub time: u64,
+    pub text: String
+}
+
+pub enum HistoryMovement {
+    Previous,
+    Next
+}
+
+/// What was searched, oldest first, saved to a file after each search
+pub struct History {
+    current: usize,
+    entries: Vec<Entry>,
+    max: usize,
+    path: Option<PathBuf>
+}
+
+impl History {
+    pub fn new(max: usize) -> Self {
+        Self {
+            current: 0,
+            entries: vec![],
+            max,
+            path: None
+        }
+    }
+
+    /// The history saved in path, if any
+    pub fn load(path: PathBuf, max: usize) -> Self {
+        let mut history = Self::new(max);
+
+        // Lines are the time, a tab and the text
+        if let Ok(content) = fs::read_to_string(&path) {
+            for line in content.lines() {
+                let entry = line.split_once('\t').and_then(|(time, text)| {
+                    Some(Entry { time: time.parse().ok()?, text: text.to_owned() })
+                });
+                history.entries.extend(entry);
+            }
+        }
+
+        let len = history.entries.len();
+        history.entries.drain(..len.saturating_sub(max));
+        history.current = history.entries.len();
+        history.path = Some(path);
+
+        history
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(())
+        };
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let content: String = self.entries.iter()
+            .map(|entry| format!("{}\t{}\n", entry.time, entry.text))
+            .collect();
+        fs::write(path, content)
+    }
+
+    /// Adds text as the latest entry, once. The history is saved, as far
+    /// as possible.
+    pub fn push(&mut self, text: String) {
+        let text = text.replace('\n', " ");
+        let time = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        self.entries.retain(|entry| entry.text != text);
+        self.entries.push(Entry { time, text });
+
+        let len = self.entries.len();
+        self.entries.drain(..len.saturating_sub(self.max));
+        self.current = self.entries.len().saturating_sub(1);
+
+        self.save().ok();
+    }
+
+    pub fn goto(&mut self, m: HistoryMovement) {
+        match m {
+            HistoryMovement::Next => {
+                if self.current + 1 < self.entries.len() {
+                    self.current += 1;
+                }
+            },
+            HistoryMovement::Previous => {
+                if self.current > 0 {
+                    self.current -= 1;
+                }
+            }
+        }
+    }
+
+    pub fn current(&self) -> Option<&String> {
+        self.entries.get(self.current).map(|entry| &entry.text)
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Entry> {
+        self.entries.get(index)
+    }
+
+    /// Index of the latest entry before the one at before, that contains
+    /// query
+    pub fn search(&self, query: &str, before: usize) -> Option<usize> {
+        let before = before.min(self.entries.len());
+        self.entries[..before].iter().rposition(|entry| entry.text.contains(query))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+/// A reverse incremental search through the history
+pub struct HistorySearch {
+    pub query: String,
+    /// Index of the entry found
+    pub found: Option<usize>,
+    /// The search bar text, to put back when the search is cancelled
+    pub original: String
+}
+
+impl HistorySearch {
+    pub fn new(original: String) -> Self {
+        HistorySearch {
+            query: String::new(),
+            found: None,
+            original
+        }
+    }
+
+    /// Looks for the query again, from the latest entry
+    pub fn update(&mut self, history: &History) {
+        self.found = history.search(&self.query, history.len());
+    }
+
+    /// Looks for an older entry with the query, if any
+    pub fn older(&mut self, history: &History) {
+        let before = self.found.unwrap_or_else(|| history.len());
+        if let Some(found) = history.search(&self.query, before) {
+            self.found = Some(found);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+
+    fn texts(history: &History) -> Vec<&str> {
+        history.entries.iter().map(|entry| entry.text.as_str()).collect()
+    }
+
+    #[test]
+    fn push_once() {
+        let mut history = History::new(10);
+        history.push(String::from("tie"));
+        history.push(String::from("tin"));
+        history.push(String::from("tie"));
+
+        assert_eq!(texts(&history), vec!["tin", "tie"]);
+        assert_eq!(history.current(), Some(&String::from("tie")));
+    }
+
+    #[test]
+    fn max_size() {
+        let mut history = History::new(2);
+        history.push(String::from("tie"));
+        history.push(String::from("tin"));
+        history.push(String::from("tip"));
+
+        assert_eq!(texts(&history), vec!["tin", "tip"]);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path = env::temp_dir().join(format!("redict-history-{}", std::process::id()));
+        fs::remove_file(&path).ok();
+
+        let mut history = History::load(path.clone(), 10);
+        history.push(String::from("tie @wn"));
+        history.push(String::from("tin"));
+
+        let history = History::load(path.clone(), 1);
+        fs::remove_file(&path).ok();
+
+        assert_eq!(texts(&history), vec!["tin"]);
+        assert!(history.get(0).unwrap().time > 0);
+        // Nothing selected until moving in the history
+        assert_eq!(history.current(), None);
+    }
+
+    #[test]
+    fn reverse_search() {
+        let mut history = History::new(10);
+        history.push(String::from("tie @wn"));
+        history.push(String::from("tin"));
+        history.push(String::from("tip @wn"));
+
+        let mut search = HistorySearch::new(String::new());
+        search.query.push_str("@wn");
+        search.update(&history);
+        assert_eq!(search.found, Some(2));
+
+        search.older(&history);
+        assert_eq!(search.found, Some(0));
+        // Nothing older, the entry stays
+        search.older(&history);
+        assert_eq!(search.found, Some(0));
+
+        search.query.push('x');
+        search.update(&history);
+        assert_eq!(search.found, None);
+    }
+}
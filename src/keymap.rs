@@ -0,0 +1,837 @@
+use crate::app::AppMode;
+
+use termion::event::Key;
+
+use std::collections::BTreeMap;
+
+/// What a key does
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Quit,
+    /// Stops the running request, or quits when there is none
+    CancelOrQuit,
+    Cancel,
+    Help,
+    /// Runs what is in the search bar, as the mode does
+    Run,
+    ScrollUp,
+    ScrollDown,
+    /// Completes the word, or goes to the next mode
+    Complete,
+    CompleteBackward,
+    NextMode,
+    PreviousMode,
+    NextServer,
+    PreviousServer,
+    AllServers,
+    Focus,
+    SelectNext,
+    SelectPrevious,
+    /// Uses the item selected, or the text typed
+    Accept,
+    DeleteBookmark,
+    Back,
+    Forward,
+    NextDefinition,
+    PreviousDefinition,
+    NextDatabase,
+    PreviousDatabase,
+    Bookmark,
+    Export,
+    Reveal,
+    Grade(u8),
+    SearchHistory,
+    HistoryPrevious,
+    HistoryNext,
+    DeleteBackward,
+    ClearSearch,
+    CursorLeft,
+    CursorRight,
+    CursorStart,
+    CursorEnd,
+    WordLeft,
+    WordRight,
+    DeleteForward,
+    KillToEnd,
+    KillWordBackward,
+    Yank,
+    /// Replaces the text yanked with older text
+    YankPop,
+    Transpose,
+    Undo,
+    Redo,
+    NormalMode,
+    Insert,
+    Append,
+    InsertStart,
+    AppendEnd,
+    LineUp,
+    LineDown,
+    Top,
+    Bottom,
+    /// Starts a search in the text shown
+    Find
+}
+
+// Names in the configuration, and descriptions in the help
+const ACTIONS: &[(Action, &str, &str)] = &[
+    (Action::Quit, "quit", "Quit"),
+    (Action::CancelOrQuit, "cancel-or-quit", "Stop the request, or quit"),
+    (Action::Cancel, "cancel", "Stop the request, or leave"),
+    (Action::Help, "help", "Show the keys"),
+    (Action::Run, "run", "Search"),
+    (Action::ScrollUp, "scroll-up", "Scroll up"),
+    (Action::ScrollDown, "scroll-down", "Scroll down"),
+    (Action::Complete, "complete", "Complete, or next mode"),
+    (Action::CompleteBackward, "complete-backward", "Complete backward, or previous mode"),
+    (Action::NextMode, "next-mode", "Next mode"),
+    (Action::PreviousMode, "previous-mode", "Previous mode"),
+    (Action::NextServer, "next-server", "Next server"),
+    (Action::PreviousServer, "previous-server", "Previous server"),
+    (Action::AllServers, "all-servers", "Use all the servers, or one"),
+    (Action::Focus, "focus", "Focus the lists"),
+    (Action::SelectNext, "select-next", "Select the next item"),
+    (Action::SelectPrevious, "select-previous", "Select the previous item"),
+    (Action::Accept, "accept", "Use the item selected, or the text typed"),
+    (Action::DeleteBookmark, "delete-bookmark", "Delete the word selected"),
+    (Action::Back, "back", "Previous definition visited"),
+    (Action::Forward, "forward", "Next definition visited"),
+    (Action::NextDefinition, "next-definition", "Next definition"),
+    (Action::PreviousDefinition, "previous-definition", "Previous definition"),
+    (Action::NextDatabase, "next-database", "Next database"),
+    (Action::PreviousDatabase, "previous-database", "Previous database"),
+    (Action::Bookmark, "bookmark", "Bookmark the definition"),
+    (Action::Export, "export", "Export the word list"),
+    (Action::Reveal, "reveal", "Reveal the definition"),
+    (Action::Grade(0), "grade-0", "Not recalled at all"),
+    (Action::Grade(1), "grade-1", "Not recalled, but familiar"),
+    (Action::Grade(2), "grade-2", "Not recalled, but easy once seen"),
+    (Action::Grade(3), "grade-3", "Recalled with difficulty"),
+    (Action::Grade(4), "grade-4", "Recalled after a while"),
+    (Action::Grade(5), "grade-5", "Recalled perfectly"),
+    (Action::SearchHistory, "search-history", "Search the history"),
+    (Action::HistoryPrevious, "history-previous", "Previous search"),
+    (Action::HistoryNext, "history-next", "Next search"),
+    (Action::DeleteBackward, "delete-backward", "Delete the character before the cursor"),
+    (Action::ClearSearch, "clear-search", "Clear the search bar"),
+    (Action::CursorLeft, "cursor-left", "Move left"),
+    (Action::CursorRight, "cursor-right", "Move right"),
+    (Action::CursorStart, "cursor-start", "Move to the start"),
+    (Action::CursorEnd, "cursor-end", "Move to the end"),
+    (Action::WordLeft, "word-left", "Move to the previous word"),
+    (Action::WordRight, "word-right", "Move past the next word"),
+    (Action::DeleteForward, "delete-forward", "Delete the character under the cursor"),
+    (Action::KillToEnd, "kill-to-end", "Cut to the end"),
+    (Action::KillWordBackward, "kill-word-backward", "Cut the word before the cursor"),
+    (Action::Yank, "yank", "Paste the text cut last"),
+    (Action::YankPop, "yank-pop", "Paste the text cut before instead"),
+    (Action::Transpose, "transpose", "Swap the characters around the cursor"),
+    (Action::Undo, "undo", "Undo the last edit"),
+    (Action::Redo, "redo", "Redo the edit undone"),
+    (Action::NormalMode, "normal-mode", "Leave the insert mode"),
+    (Action::Insert, "insert", "Insert before the cursor"),
+    (Action::Append, "append", "Insert after the cursor"),
+    (Action::InsertStart, "insert-start", "Insert at the start"),
+    (Action::AppendEnd, "append-end", "Insert at the end"),
+    (Action::LineUp, "line-up", "Scroll up a line"),
+    (Action::LineDown, "line-down", "Scroll down a line"),
+    (Action::Top, "top", "Go to the top"),
+    (Action::Bottom, "bottom", "Go to the bottom"),
+    (Action::Find, "find", "Search the text, or search again")
+];
+
+impl Action {
+    pub fn name(&self) -> &'static str {
+        ACTIONS.iter().find(|(a, _, _)| a == self).map_or("", |(_, name, _)| name)
+    }
+
+    pub fn description(&self) -> &'static str {
+        ACTIONS.iter().find(|(a, _, _)| a == self).map_or("", |(_, _, desc)| desc)
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        ACTIONS.iter().find(|(_, n, _)| *n == name).map(|(a, _, _)| *a)
+    }
+
+    // Actions are listed in that order in the help
+    fn rank(&self) -> usize {
+        ACTIONS.iter().position(|(a, _, _)| a == self).unwrap_or(ACTIONS.len())
+    }
+
+    // Done as many times as a count before the keys says
+    fn repeats(&self) -> bool {
+        use Action::*;
+
+        matches!(self, ScrollUp | ScrollDown | NextMode | PreviousMode | NextServer | PreviousServer
+            | SelectNext | SelectPrevious | Back | Forward | NextDefinition | PreviousDefinition | NextDatabase | PreviousDatabase
+            | HistoryPrevious | HistoryNext | DeleteBackward | DeleteForward | CursorLeft | CursorRight
+            | WordLeft | WordRight | KillWordBackward | Transpose | Undo | Redo | LineUp | LineDown)
+    }
+}
+
+/// A key as pressed, with the arrows that termion does not decode with
+/// Alt held
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyPress {
+    Key(Key),
+    Alt(Key)
+}
+
+// Control with these sends the bytes after C-z, that termion reads as
+// digits
+const CTRL_SYMBOLS: &[(char, char)] = &[('\\', '4'), (']', '5'), ('^', '6'), ('_', '7'), ('/', '7')];
+
+fn parse_key(name: &str) -> Result<KeyPress, String> {
+    let special = |name: &str| match name {
+        "Enter" => Some(Key::Char('\n')),
+        "Tab" => Some(Key::Char('\t')),
+        "Space" => Some(Key::Char(' ')),
+        "BackTab" => Some(Key::BackTab),
+        "Esc" => Some(Key::Esc),
+        "Backspace" => Some(Key::Backspace),
+        "Delete" => Some(Key::Delete),
+        "Insert" => Some(Key::Insert),
+        "Left" => Some(Key::Left),
+        "Right" => Some(Key::Right),
+        "Up" => Some(Key::Up),
+        "Down" => Some(Key::Down),
+        "Home" => Some(Key::Home),
+        "End" => Some(Key::End),
+        "PageUp" => Some(Key::PageUp),
+        "PageDown" => Some(Key::PageDown),
+        _ => match name.strip_prefix('F').map(str::parse) {
+            Some(Ok(n)) => Some(Key::F(n)),
+            _ => None
+        }
+    };
+
+    let single = |name: &str| {
+        let mut chars = name.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None
+        }
+    };
+
+    let key = if let Some(key) = special(name) {
+        Some(KeyPress::Key(key))
+    } else if let Some(rest) = name.strip_prefix("C-") {
+        single(rest).map(|c| {
+            let c = CTRL_SYMBOLS.iter().find(|(symbol, _)| *symbol == c).map_or(c, |(_, digit)| *digit);
+            KeyPress::Key(Key::Ctrl(c.to_ascii_lowercase()))
+        })
+    } else if let Some(rest) = name.strip_prefix("A-") {
+        match (single(rest), special(rest)) {
+            (Some(c), _) => Some(KeyPress::Key(Key::Alt(c))),
+            (None, Some(key)) => Some(KeyPress::Alt(key)),
+            _ => None
+        }
+    } else {
+        single(name).map(|c| KeyPress::Key(Key::Char(c)))
+    };
+
+    key.ok_or_else(|| format!("Unknown key {}", name))
+}
+
+fn key_name(key: KeyPress) -> String {
+    let special = |key: Key| match key {
+        Key::Char('\n') => String::from("Enter"),
+        Key::Char('\t') => String::from("Tab"),
+        Key::Char(' ') => String::from("Space"),
+        Key::Char(c) => c.to_string(),
+        Key::Ctrl(c) => {
+            let c = CTRL_SYMBOLS.iter().find(|(_, digit)| *digit == c).map_or(c, |(symbol, _)| *symbol);
+            format!("C-{}", c)
+        },
+        Key::Alt(c) => format!("A-{}", c),
+        Key::F(n) => format!("F{}", n),
+        key => format!("{:?}", key)
+    };
+
+    match key {
+        KeyPress::Key(key) => special(key),
+        KeyPress::Alt(key) => format!("A-{}", special(key))
+    }
+}
+
+/// Keys separated by spaces, as in `C-x C-e`
+fn parse_keys(names: &str) -> Result<Vec<KeyPress>, String> {
+    let keys: Vec<KeyPress> = names.split_whitespace().map(parse_key).collect::<Result<_, _>>()?;
+
+    if keys.is_empty() {
+        return Err(String::from("No key"));
+    }
+    Ok(keys)
+}
+
+pub fn keys_name(keys: &[KeyPress]) -> String {
+    keys.iter().map(|&key| key_name(key)).collect::<Vec<String>>().join(" ")
+}
+
+/// What takes the keys before the modes, while it is shown
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layer {
+    /// The focused list
+    List,
+    Suggestions,
+    HistorySearch,
+    /// The prompt of the search in the text
+    Find
+}
+
+impl Layer {
+    const ALL: [Layer; 4] = [Layer::List, Layer::Suggestions, Layer::HistorySearch, Layer::Find];
+
+    /// The name of its table in the configuration
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layer::List => "list",
+            Layer::Suggestions => "suggestions",
+            Layer::HistorySearch => "history-search",
+            Layer::Find => "find"
+        }
+    }
+}
+
+/// What a key pressed led to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step {
+    Action(Action),
+    /// A key bound to nothing
+    Key(KeyPress)
+}
+
+/// The modes of vi: keys do things in the normal one, and type in the
+/// insert one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ViMode {
+    Normal,
+    Insert
+}
+
+impl ViMode {
+    pub fn text(&self) -> &'static str {
+        match self {
+            ViMode::Normal => "NORMAL",
+            ViMode::Insert => "INSERT"
+        }
+    }
+}
+
+// Counts stop there
+const MAX_COUNT: usize = 9999;
+
+struct Binding {
+    /// Bindings of the modes have none
+    layer: Option<Layer>,
+    /// Bindings for all the modes have none
+    mode: Option<AppMode>,
+    /// Bindings for all of vi's modes, or without them, have none
+    vi: Option<ViMode>,
+    keys: Vec<KeyPress>,
+    action: Action
+}
+
+/// The keys of each mode, and of all of them
+pub struct Keymap {
+    bindings: Vec<Binding>,
+    /// The start of a sequence
+    pending: Vec<KeyPress>,
+    /// Without vi's modes, none
+    vi: Option<ViMode>,
+    /// Typed before the keys, in vi's normal mode
+    count: Option<usize>
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        use Action::*;
+        use Key::*;
+
+        let all = [
+            (Ctrl('c'), Quit),
+            (Ctrl('q'), Quit),
+            (Esc, CancelOrQuit),
+            (Ctrl('g'), Cancel),
+            (Char('?'), Help),
+            (F(1), Help),
+            (PageUp, ScrollUp),
+            (PageDown, ScrollDown),
+            (Char('\t'), Complete),
+            (BackTab, CompleteBackward),
+            (Ctrl('n'), NextServer),
+            (Ctrl('p'), PreviousServer),
+            (Ctrl('s'), AllServers),
+            (Alt('s'), Bookmark),
+            (Ctrl('o'), Focus),
+            (Ctrl('r'), SearchHistory),
+            (Down, HistoryPrevious),
+            (Up, HistoryNext),
+            (Backspace, DeleteBackward),
+            (Ctrl('u'), ClearSearch),
+            (Left, CursorLeft),
+            (Right, CursorRight),
+            (Ctrl('b'), CursorStart),
+            (Home, CursorStart),
+            (Ctrl('e'), CursorEnd),
+            (End, CursorEnd),
+            (Alt('b'), WordLeft),
+            (Alt('f'), WordRight),
+            (Delete, DeleteForward),
+            (Ctrl('d'), DeleteForward),
+            (Ctrl('k'), KillToEnd),
+            (Ctrl('w'), KillWordBackward),
+            (Ctrl('y'), Yank),
+            (Alt('y'), YankPop),
+            (Ctrl('t'), Transpose),
+            (Ctrl('7'), Undo),
+            (Alt('_'), Redo)
+        ];
+
+        let modes = [
+            (AppMode::Define, Char('\n'), Run),
+            (AppMode::Define, Ctrl('l'), NextDefinition),
+            (AppMode::Define, Ctrl('h'), PreviousDefinition),
+            (AppMode::Match, Char('\n'), Run),
+            (AppMode::Info, Char('\n'), Run),
+            (AppMode::Info, Ctrl('l'), NextDatabase),
+            (AppMode::Info, Ctrl('h'), PreviousDatabase),
+            (AppMode::Command, Char('\n'), Run),
+            (AppMode::Bookmarks, Char('\n'), Run),
+            (AppMode::Bookmarks, Alt('x'), Export),
+            (AppMode::Quiz, Char('\n'), Reveal),
+            (AppMode::Quiz, Char(' '), Reveal),
+            (AppMode::Quiz, Char('0'), Grade(0)),
+            (AppMode::Quiz, Char('1'), Grade(1)),
+            (AppMode::Quiz, Char('2'), Grade(2)),
+            (AppMode::Quiz, Char('3'), Grade(3)),
+            (AppMode::Quiz, Char('4'), Grade(4)),
+            (AppMode::Quiz, Char('5'), Grade(5))
+        ];
+
+        let normal = [
+            (Esc, Cancel),
+            (Char('h'), CursorLeft),
+            (Char('l'), CursorRight),
+            (Char('0'), CursorStart),
+            (Char('$'), CursorEnd),
+            (Char('x'), DeleteForward),
+            (Char('i'), Action::Insert),
+            (Char('a'), Append),
+            (Char('I'), InsertStart),
+            (Char('A'), AppendEnd),
+            (Char('j'), LineDown),
+            (Char('k'), LineUp),
+            (Char('G'), Bottom),
+            (Ctrl('d'), ScrollDown),
+            (Ctrl('u'), ScrollUp),
+            (Char('n'), NextDefinition),
+            (Char('N'), PreviousDefinition),
+            (Char('/'), Find)
+        ];
+
+        let layers = [
+            (Layer::List, Char('j'), SelectNext),
+            (Layer::List, Down, SelectNext),
+            (Layer::List, Char('\t'), SelectNext),
+            (Layer::List, Char('k'), SelectPrevious),
+            (Layer::List, Up, SelectPrevious),
+            (Layer::List, BackTab, SelectPrevious),
+            (Layer::List, Char('\n'), Accept),
+            (Layer::List, Esc, Cancel),
+            (Layer::List, Delete, DeleteBookmark),
+            (Layer::Suggestions, Down, SelectNext),
+            (Layer::Suggestions, Up, SelectPrevious),
+            (Layer::Suggestions, Char('\n'), Accept),
+            (Layer::Suggestions, Esc, Cancel),
+            (Layer::HistorySearch, Ctrl('r'), SearchHistory),
+            (Layer::HistorySearch, Backspace, DeleteBackward),
+            (Layer::HistorySearch, Esc, Cancel),
+            (Layer::HistorySearch, Ctrl('g'), Cancel),
+            (Layer::Find, Char('\n'), Accept),
+            (Layer::Find, Backspace, DeleteBackward),
+            (Layer::Find, Esc, Cancel),
+            (Layer::Find, Ctrl('g'), Cancel)
+        ];
+
+        let mut bindings: Vec<Binding> = all.iter()
+            .map(|&(key, action)| Binding { layer: None, mode: None, vi: None, keys: vec![KeyPress::Key(key)], action })
+            .chain(modes.iter().map(|&(mode, key, action)| {
+                Binding { layer: None, mode: Some(mode), vi: None, keys: vec![KeyPress::Key(key)], action }
+            }))
+            .chain(normal.iter().map(|&(key, action)| {
+                Binding { layer: None, mode: None, vi: Some(ViMode::Normal), keys: vec![KeyPress::Key(key)], action }
+            }))
+            .chain(layers.iter().map(|&(layer, key, action)| {
+                Binding { layer: Some(layer), mode: None, vi: None, keys: vec![KeyPress::Key(key)], action }
+            }))
+            .collect();
+        bindings.push(Binding { layer: None, mode: None, vi: None, keys: vec![KeyPress::Alt(Left)], action: Back });
+        bindings.push(Binding { layer: None, mode: None, vi: None, keys: vec![KeyPress::Alt(Right)], action: Forward });
+        bindings.push(Binding {
+            layer: None,
+            mode: None,
+            vi: Some(ViMode::Normal),
+            keys: vec![KeyPress::Key(Char('g')), KeyPress::Key(Char('g'))],
+            action: Top
+        });
+        bindings.push(Binding { layer: None, mode: None, vi: Some(ViMode::Insert), keys: vec![KeyPress::Key(Esc)], action: NormalMode });
+
+        Keymap {
+            bindings,
+            pending: Vec::new(),
+            vi: None,
+            count: None
+        }
+    }
+}
+
+impl Keymap {
+    /// The default keys, changed by those of the configuration: for all
+    /// the modes under `all`, under the name of each mode, under `normal`
+    /// and `insert` for vi's modes, and under the name of each layer.
+    /// Keys bound to `none` do nothing.
+    pub fn new(config: &BTreeMap<String, BTreeMap<String, String>>) -> Result<Self, String> {
+        let mut keymap = Self::default();
+
+        for (mode_name, keys) in config {
+            let (layer, mode, vi) = match mode_name.as_str() {
+                "all" => (None, None, None),
+                "normal" => (None, None, Some(ViMode::Normal)),
+                "insert" => (None, None, Some(ViMode::Insert)),
+                name => match (
+                    Layer::ALL.iter().find(|l| l.name() == name),
+                    AppMode::values().into_iter().find(|m| m.text().eq_ignore_ascii_case(name))
+                ) {
+                    (Some(&layer), _) => (Some(layer), None, None),
+                    (None, Some(mode)) => (None, Some(mode), None),
+                    (None, None) => return Err(format!("Unknown mode {}", name))
+                }
+            };
+
+            for (names, action_name) in keys {
+                let keys = parse_keys(names)?;
+                // Layers take the keys one at a time
+                if layer.is_some() && keys.len() > 1 {
+                    return Err(format!("No sequence in {}: {}", mode_name, names));
+                }
+                keymap.bindings.retain(|b| b.layer != layer || b.mode != mode || b.vi != vi || b.keys != keys);
+
+                if action_name == "none" {
+                    continue;
+                }
+                let action = Action::parse(action_name)
+                    .ok_or_else(|| format!("Unknown action {}", action_name))?;
+                keymap.bindings.push(Binding { layer, mode, vi, keys, action });
+            }
+        }
+
+        Ok(keymap)
+    }
+
+    /// Uses vi's modes, from the insert one
+    pub fn vi(mut self) -> Self {
+        self.vi = Some(ViMode::Insert);
+        self
+    }
+
+    pub fn vi_mode(&self) -> Option<ViMode> {
+        self.vi
+    }
+
+    /// Goes to a mode of vi, if they are used
+    pub fn set_vi_mode(&mut self, vi: ViMode) {
+        if self.vi.is_some() {
+            self.vi = Some(vi);
+            self.pending.clear();
+            self.count = None;
+        }
+    }
+
+    fn bindings(&self, mode: AppMode) -> impl Iterator<Item = &Binding> {
+        self.bindings.iter().filter(move |b| {
+            b.layer.is_none() && (b.mode.is_none() || b.mode == Some(mode)) && (b.vi.is_none() || b.vi == self.vi)
+        })
+    }
+
+    fn layer_bindings(&self, layer: Layer) -> impl Iterator<Item = &Binding> {
+        self.bindings.iter().filter(move |b| b.layer == Some(layer))
+    }
+
+    // Bindings of the mode come first, then those of vi's mode
+    fn action(&self, mode: AppMode, keys: &[KeyPress]) -> Option<Action> {
+        self.bindings(mode)
+            .filter(|b| b.keys == keys)
+            .min_by_key(|b| (b.mode.is_none(), b.vi.is_none()))
+            .map(|b| b.action)
+    }
+
+    // Whether a digit adds to the count rather than being a key
+    fn counts(&self, mode: AppMode, key: KeyPress) -> bool {
+        let digit = match key {
+            KeyPress::Key(Key::Char(c)) => c.to_digit(10),
+            _ => None
+        };
+
+        match (self.vi, digit) {
+            (Some(ViMode::Normal), Some(digit)) => {
+                self.pending.is_empty()
+                    && (digit > 0 || self.count.is_some())
+                    && !self.bindings(mode).any(|b| b.mode.is_some() && b.keys == [key])
+            },
+            _ => false
+        }
+    }
+
+    // Whether longer sequences start with keys
+    fn starts(&self, mode: AppMode, keys: &[KeyPress]) -> bool {
+        self.bindings(mode).any(|b| b.keys.len() > keys.len() && b.keys.starts_with(keys))
+    }
+
+    /// What the keys pressed lead to. A sequence that goes nowhere gives
+    /// its first key, and the rest is looked up again.
+    pub fn feed(&mut self, mode: AppMode, key: KeyPress) -> Vec<Step> {
+        if self.counts(mode, key) {
+            if let KeyPress::Key(Key::Char(c)) = key {
+                let count = self.count.unwrap_or(0) * 10 + c.to_digit(10).unwrap_or(0) as usize;
+                self.count = Some(count.min(MAX_COUNT));
+            }
+            return Vec::new();
+        }
+
+        // Esc drops the count only
+        if self.count.is_some() && key == KeyPress::Key(Key::Esc) {
+            self.count = None;
+            return Vec::new();
+        }
+
+        self.pending.push(key);
+        let mut steps = Vec::new();
+
+        while !self.pending.is_empty() {
+            if self.starts(mode, &self.pending) {
+                break;
+            }
+
+            if let Some(action) = self.action(mode, &self.pending) {
+                steps.push(Step::Action(action));
+                self.pending.clear();
+            } else {
+                // Maybe bound alone, but waiting for the rest of a sequence
+                let first = self.pending.remove(0);
+                steps.push(match self.action(mode, &[first]) {
+                    Some(action) => Step::Action(action),
+                    None => Step::Key(first)
+                });
+            }
+        }
+
+        if steps.is_empty() {
+            return steps;
+        }
+
+        let count = self.count.take().unwrap_or(1);
+        steps.into_iter()
+            .flat_map(|step| {
+                let times = match step {
+                    Step::Action(action) if action.repeats() => count,
+                    _ => 1
+                };
+                std::iter::repeat(step).take(times)
+            })
+            .collect()
+    }
+
+    /// The start of a sequence, if any
+    pub fn pending(&self) -> &[KeyPress] {
+        &self.pending
+    }
+
+    /// The count typed, if any
+    pub fn count(&self) -> Option<usize> {
+        self.count
+    }
+
+    /// Whether key alone does action in mode
+    pub fn does(&self, mode: AppMode, key: KeyPress, action: Action) -> bool {
+        self.action(mode, &[key]) == Some(action)
+    }
+
+    /// What key does in layer. Unless it is bound there, it goes on to the
+    /// mode.
+    pub fn layer_action(&self, layer: Layer, key: KeyPress) -> Option<Action> {
+        self.layer_bindings(layer).find(|b| b.keys == [key]).map(|b| b.action)
+    }
+
+    /// The keys of each action available in mode, for the help, those of
+    /// the layer shown first
+    pub fn help(&self, mode: AppMode, layer: Option<Layer>) -> Vec<(String, Action)> {
+        let mut layered: Vec<&Binding> = match layer {
+            Some(layer) => self.layer_bindings(layer).collect(),
+            None => Vec::new()
+        };
+        layered.sort_by_key(|b| b.action.rank());
+
+        // Unless the mode or the layer binds the keys to something else
+        let mut bindings: Vec<&Binding> = self.bindings(mode)
+            .filter(|b| self.action(mode, &b.keys) == Some(b.action))
+            .filter(|b| !layered.iter().any(|l| l.keys == b.keys))
+            .collect();
+        bindings.sort_by_key(|b| b.action.rank());
+        layered.append(&mut bindings);
+        let bindings = layered;
+
+        let mut help: Vec<(String, Action)> = Vec::new();
+        for binding in bindings {
+            let keys = keys_name(&binding.keys);
+            match help.last_mut() {
+                Some((names, action)) if *action == binding.action => {
+                    names.push_str(", ");
+                    names.push_str(&keys);
+                },
+                _ => help.push((keys, binding.action))
+            }
+        }
+
+        help
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn keys(mode: &str, bindings: &[(&str, &str)]) -> BTreeMap<String, BTreeMap<String, String>> {
+        let keys = bindings.iter().map(|(k, a)| (k.to_string(), a.to_string())).collect();
+        vec![(mode.to_owned(), keys)].into_iter().collect()
+    }
+
+    #[test]
+    fn key_names() {
+        for name in &["C-l", "A-s", "A-Left", "Enter", "Space", "?", "F1", "BackTab", "PageDown", "C-_"] {
+            assert_eq!(key_name(parse_key(name).unwrap()), *name);
+        }
+        assert_eq!(parse_key("C-L"), Ok(KeyPress::Key(Key::Ctrl('l'))));
+        assert_eq!(parse_key("C-/"), Ok(KeyPress::Key(Key::Ctrl('7'))));
+        assert!(parse_key("C-").is_err());
+        assert!(parse_key("Foo").is_err());
+    }
+
+    #[test]
+    fn mode_bindings_first() {
+        let mut keymap = Keymap::default();
+        let key = KeyPress::Key(Key::Ctrl('l'));
+
+        assert_eq!(keymap.feed(AppMode::Define, key), vec![Step::Action(Action::NextDefinition)]);
+        assert_eq!(keymap.feed(AppMode::Info, key), vec![Step::Action(Action::NextDatabase)]);
+        assert_eq!(keymap.feed(AppMode::Match, key), vec![Step::Key(key)]);
+    }
+
+    #[test]
+    fn configured_bindings() {
+        let config = keys("define", &[("C-l", "none"), ("C-n", "next-definition")]);
+        let mut keymap = Keymap::new(&config).unwrap();
+
+        assert_eq!(keymap.feed(AppMode::Define, KeyPress::Key(Key::Ctrl('l'))), vec![Step::Key(KeyPress::Key(Key::Ctrl('l')))]);
+        assert_eq!(keymap.feed(AppMode::Define, KeyPress::Key(Key::Ctrl('n'))), vec![Step::Action(Action::NextDefinition)]);
+        // Still there in the other modes
+        assert_eq!(keymap.feed(AppMode::Match, KeyPress::Key(Key::Ctrl('n'))), vec![Step::Action(Action::NextServer)]);
+
+        assert!(Keymap::new(&keys("define", &[("C-n", "nothing")])).is_err());
+        assert!(Keymap::new(&keys("nowhere", &[("C-n", "quit")])).is_err());
+    }
+
+    #[test]
+    fn sequences() {
+        let mut keymap = Keymap::new(&keys("all", &[("C-x C-n", "next-mode")])).unwrap();
+        let ctrl_x = KeyPress::Key(Key::Ctrl('x'));
+
+        assert_eq!(keymap.feed(AppMode::Define, ctrl_x), vec![]);
+        assert_eq!(keymap.pending(), &[ctrl_x]);
+        assert_eq!(keymap.feed(AppMode::Define, KeyPress::Key(Key::Ctrl('n'))), vec![Step::Action(Action::NextMode)]);
+
+        // Going nowhere, the keys are taken one by one
+        keymap.feed(AppMode::Define, ctrl_x);
+        assert_eq!(keymap.feed(AppMode::Define, KeyPress::Key(Key::Ctrl('l'))), vec![
+            Step::Key(ctrl_x),
+            Step::Action(Action::NextDefinition)
+        ]);
+        assert!(keymap.pending().is_empty());
+    }
+
+    #[test]
+    fn vi_modes() {
+        let mut keymap = Keymap::default().vi();
+        let char = |c| KeyPress::Key(Key::Char(c));
+        let esc = KeyPress::Key(Key::Esc);
+
+        // Typed in the insert mode
+        assert_eq!(keymap.feed(AppMode::Define, char('j')), vec![Step::Key(char('j'))]);
+        assert_eq!(keymap.feed(AppMode::Define, esc), vec![Step::Action(Action::NormalMode)]);
+
+        keymap.set_vi_mode(ViMode::Normal);
+        assert_eq!(keymap.feed(AppMode::Define, char('j')), vec![Step::Action(Action::LineDown)]);
+        assert_eq!(keymap.feed(AppMode::Define, esc), vec![Step::Action(Action::Cancel)]);
+        // The mode comes first
+        assert_eq!(keymap.feed(AppMode::Quiz, char('2')), vec![Step::Action(Action::Grade(2))]);
+
+        // Without vi, nothing
+        let mut plain = Keymap::default();
+        assert_eq!(plain.feed(AppMode::Define, char('j')), vec![Step::Key(char('j'))]);
+    }
+
+    #[test]
+    fn counts() {
+        let mut keymap = Keymap::default().vi();
+        keymap.set_vi_mode(ViMode::Normal);
+        let char = |c| KeyPress::Key(Key::Char(c));
+
+        assert_eq!(keymap.feed(AppMode::Define, char('1')), vec![]);
+        assert_eq!(keymap.feed(AppMode::Define, char('0')), vec![]);
+        assert_eq!(keymap.count(), Some(10));
+        assert_eq!(keymap.feed(AppMode::Define, char('k')), vec![Step::Action(Action::LineUp); 10]);
+        assert_eq!(keymap.count(), None);
+
+        // 0 alone is a key
+        assert_eq!(keymap.feed(AppMode::Define, char('0')), vec![Step::Action(Action::CursorStart)]);
+
+        // Through sequences, but not for every action
+        keymap.feed(AppMode::Define, char('3'));
+        assert_eq!(keymap.feed(AppMode::Define, char('g')), vec![]);
+        assert_eq!(keymap.feed(AppMode::Define, char('g')), vec![Step::Action(Action::Top)]);
+
+        keymap.feed(AppMode::Define, char('3'));
+        assert_eq!(keymap.feed(AppMode::Define, KeyPress::Key(Key::Esc)), vec![]);
+        assert_eq!(keymap.count(), None);
+    }
+
+    #[test]
+    fn layers() {
+        let keymap = Keymap::new(&keys("list", &[("l", "select-next"), ("j", "none")])).unwrap();
+        let key = |key| KeyPress::Key(key);
+
+        assert_eq!(keymap.layer_action(Layer::List, key(Key::Char('l'))), Some(Action::SelectNext));
+        assert_eq!(keymap.layer_action(Layer::List, key(Key::Char('j'))), None);
+        assert_eq!(keymap.layer_action(Layer::Find, key(Key::Esc)), Some(Action::Cancel));
+        // Not bound in the modes
+        assert!(keymap.does(AppMode::Define, key(Key::Down), Action::HistoryPrevious));
+
+        assert!(Keymap::new(&keys("find", &[("C-x C-g", "cancel")])).is_err());
+    }
+
+    #[test]
+    fn help() {
+        let keymap = Keymap::default();
+        let help = keymap.help(AppMode::Define, None);
+
+        assert_eq!(help[0], (String::from("C-c, C-q"), Action::Quit));
+        assert!(help.contains(&(String::from("C-l"), Action::NextDefinition)));
+        assert!(!help.iter().any(|(_, action)| *action == Action::NextDatabase));
+
+        // The layer first, over the mode
+        let help = keymap.help(AppMode::Define, Some(Layer::List));
+        assert_eq!(help[0], (String::from("Esc"), Action::Cancel));
+        assert_eq!(help[1], (String::from("j, Down, Tab"), Action::SelectNext));
+        assert!(!help.iter().any(|(_, action)| *action == Action::CancelOrQuit));
+    }
+}
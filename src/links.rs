@@ -0,0 +1,115 @@
+/// A cross-reference in the text of a definition, written `{word}`. It
+/// may span several lines.
+#[derive(Debug, PartialEq)]
+pub struct Link {
+    pub word: String,
+    /// Line and byte of the opening brace
+    pub start: (usize, usize),
+    /// Line and byte of the closing brace
+    pub end: (usize, usize)
+}
+
+pub fn parse(text: &[String]) -> Vec<Link> {
+    let mut links = Vec::new();
+    let mut open: Option<((usize, usize), String)> = None;
+
+    for (i, line) in text.iter().enumerate() {
+        for (j, c) in line.char_indices() {
+            match c {
+                // An unclosed brace does not make a link
+                '{' => open = Some(((i, j), String::new())),
+                '}' => {
+                    if let Some((start, word)) = open.take() {
+                        let word = word.split_whitespace().collect::<Vec<&str>>().join(" ");
+                        if !word.is_empty() {
+                            links.push(Link { word, start, end: (i, j) });
+                        }
+                    }
+                },
+                c => {
+                    if let Some((_, ref mut word)) = open {
+                        word.push(c);
+                    }
+                }
+            }
+        }
+
+        if let Some((_, ref mut word)) = open {
+            word.push(' ');
+        }
+    }
+
+    links
+}
+
+/// Cuts a line in pieces, each with the index of the link it belongs to.
+/// The braces are left out.
+pub fn segments<'a>(index: usize, line: &'a str, links: &[Link]) -> Vec<(&'a str, Option<usize>)> {
+    let mut segments = Vec::new();
+    let mut from = 0;
+    let mut current = None;
+
+    let push = |segments: &mut Vec<_>, from: usize, to: usize, link| {
+        if to > from {
+            segments.push((&line[from..to], link));
+        }
+    };
+
+    for (j, c) in line.char_indices() {
+        let at = (index, j);
+        let link = links.iter().position(|l| l.start <= at && at <= l.end);
+        let brace = link.map_or(false, |k| links[k].start == at || links[k].end == at);
+
+        if brace || link != current {
+            push(&mut segments, from, j, current);
+            from = if brace { j + c.len_utf8() } else { j };
+            current = link;
+        }
+    }
+    push(&mut segments, from, line.len(), current);
+
+    segments
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lines(text: &[&str]) -> Vec<String> {
+        text.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_links() {
+        let text = lines(&["see {tin} and {tie}", "not {closed"]);
+        let links = parse(&text);
+
+        assert_eq!(links, vec![
+            Link { word: String::from("tin"), start: (0, 4), end: (0, 8) },
+            Link { word: String::from("tie"), start: (0, 14), end: (0, 18) }
+        ]);
+    }
+
+    #[test]
+    fn multiline_link() {
+        let text = lines(&["see {fiscal", "     year}.", "{}"]);
+        let links = parse(&text);
+
+        assert_eq!(links, vec![Link { word: String::from("fiscal year"), start: (0, 4), end: (1, 9) }]);
+    }
+
+    #[test]
+    fn segments_without_braces() {
+        let text = lines(&["see {tin} and {tie}", "{fiscal", "year}."]);
+        let links = parse(&text);
+
+        assert_eq!(segments(0, &text[0], &links), vec![
+            ("see ", None),
+            ("tin", Some(0)),
+            (" and ", None),
+            ("tie", Some(1))
+        ]);
+        assert_eq!(segments(1, &text[1], &links), vec![("fiscal", Some(2))]);
+        assert_eq!(segments(2, &text[2], &links), vec![("year", Some(2)), (".", None)]);
+    }
+}
@@ -1,30 +1,62 @@
 mod app;
+mod bookmarks;
+mod cache;
+mod completion;
+mod config;
+mod events;
+mod find;
+mod history;
+mod keymap;
+mod links;
+mod navigation;
+mod quiz;
 mod searchbar;
+mod worker;
+mod xdg;
 
 #[macro_use]
 extern crate clap;
 
 use std::io;
+use std::path::PathBuf;
 use termion::raw::IntoRawMode;
 use tui::{Terminal, Frame};
 use tui::backend::{TermionBackend, Backend};
-use tui::widgets::{Block, Borders, Paragraph, Tabs};
+use tui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Tabs};
 use tui::text::{Span, Spans};
-use tui::style::{Style, Color};
+use tui::style::{Style, Color, Modifier};
 use tui::layout::{Layout, Constraint, Direction, Rect};
 use termion::event::Key;
-use termion::input::TermRead;
 use termion::screen::AlternateScreen;
-use app::{App, HistoryMovement, AppMode};
+use app::{App, AppMode, Focus};
+use bookmarks::Bookmarks;
+use cache::Cache;
+use config::Config;
+use history::{History, HistoryMovement};
+use keymap::{Action, Keymap, KeyPress, Layer, Step, ViMode};
+use quiz::Deck;
 use dictproto::url::DICTUrl;
+use dictproto::connection::RawAnswer;
+use dictproto::transcript::Recorder;
 use searchbar::CursorDirection;
+use events::{Event, Events};
 
-fn make_block(name: &str) -> Block {
+fn make_block(name: &str) -> Block<'_> {
     Block::default()
         .borders(Borders::ALL)
         .title(name)
 }
 
+// The block of a list, outlined while it has the focus
+fn list_block(name: &str, focused: bool) -> Block<'_> {
+    let block = make_block(name);
+    if focused {
+        block.border_style(Style::default().fg(Color::Blue))
+    } else {
+        block
+    }
+}
+
 fn draw_definitions<B: Backend>(f: &mut Frame<B>, rect: Rect, app: &App) {
 
     let chunks = Layout::default()
@@ -37,7 +69,7 @@ fn draw_definitions<B: Backend>(f: &mut Frame<B>, rect: Rect, app: &App) {
 
     let titles = app.results.iter()
         .map(|res| {
-            Spans::from(vec![Span::from(&*res.source.desc)])
+            Spans::from(vec![Span::from(app.source_label(res.server, &res.source.desc))])
         }).collect();
 
     let tabs = Tabs::new(titles)
@@ -46,107 +78,694 @@ fn draw_definitions<B: Backend>(f: &mut Frame<B>, rect: Rect, app: &App) {
         .select(app.selected_def());
     f.render_widget(tabs, chunks[1]);
 
+    // Cross-references are highlighted, the selected one reversed
+    let links = app.links();
+    let focused = app.focus() == Focus::Definition;
     let text: Vec<Spans> = app.results.get(app.selected_def()).unwrap()
         .text.iter()
-        .map(|line| { Spans::from(Span::from(String::from(line))) })
+        .enumerate()
+        .map(|(i, line)| {
+            let spans: Vec<Span> = links::segments(i, line, &links).into_iter()
+                .map(|(text, link)| match link {
+                    Some(k) if focused && app.selected_link() == Some(k) => {
+                        Span::styled(text, Style::default().fg(Color::Blue).add_modifier(Modifier::REVERSED))
+                    },
+                    Some(_) => Span::styled(text, Style::default().fg(Color::Blue)),
+                    None => Span::from(text)
+                }).collect();
+            Spans::from(spans)
+        })
         .collect();
 
     let block = Paragraph::new(text)
-        .block(make_block("Definition"))
+        .block(list_block("Definition", focused))
         .scroll((app.definition_scroll(), 0));
     f.render_widget(block, chunks[0]);
 }
 
 fn draw_matches<B: Backend>(f: &mut Frame<B>, rect: Rect, app: &App) {
-    let dbs: Vec<Spans> = app.matches.iter()
+    let matches: Vec<ListItem> = app.matches.iter()
         .map(|m| {
-            Spans::from(vec![Span::from(format!("{} ({})", m.word, m.source.name))])
+            let label = format!("{} ({})", m.word, m.source.name);
+            ListItem::new(app.source_label(m.server, &label))
         }).collect();
 
-    let block = Paragraph::new(dbs)
-        .block(Block::default().borders(Borders::ALL))
+    let list = List::new(matches)
+        .block(list_block("", app.focus() == Focus::Matches))
+        .highlight_style(Style::default().fg(Color::Blue));
+    let mut state = ListState::default();
+    if !app.matches.is_empty() {
+        state.select(Some(app.selected_match()));
+    }
+    f.render_stateful_widget(list, rect, &mut state);
+}
+
+
+fn draw_info<B: Backend>(f: &mut Frame<B>, rect: Rect, app: &App) {
+    let server = app.server();
+
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Percentage(50),
+                Constraint::Percentage(50),
+            ].as_ref()
+        ).split(rect);
+
+    let chunks = Layout::default()
+        .constraints(
+            [
+                Constraint::Percentage(40),
+                Constraint::Percentage(35),
+                Constraint::Percentage(25),
+            ].as_ref()
+        ).split(columns[0]);
+
+    // Server
+    let mut lines: Vec<Spans> = server.server_info.iter()
+        .map(|line| Spans::from(Span::from(line.as_str())))
+        .collect();
+    lines.push(Spans::from(""));
+    lines.push(Spans::from(format!("Capabilities: {}", server.capabilities.join(", "))));
+    lines.push(Spans::from(format!("Message id: {}", server.msg_id)));
+    if let Some(ref stats) = server.stats {
+        lines.push(Spans::from(format!("Statistics: {}", stats.text)));
+    }
+
+    let block = Paragraph::new(lines)
+        .block(make_block("Server"));
+    f.render_widget(block, chunks[0]);
+
+    // Databases
+    let dbs: Vec<ListItem> = server.databases.iter()
+        .map(|db| ListItem::new(format!("{} ({})", db.desc, db.name)))
+        .collect();
+    let list = List::new(dbs)
+        .block(list_block("Databases", app.focus() == Focus::Databases))
+        .highlight_style(Style::default().fg(Color::Blue));
+    let mut state = ListState::default();
+    if !server.databases.is_empty() {
+        state.select(Some(server.selected_db));
+    }
+    f.render_stateful_widget(list, chunks[1], &mut state);
+
+    // Strategies
+    let strats: Vec<ListItem> = server.stategies.iter()
+        .map(|strat| ListItem::new(format!("{} ({})", strat.desc, strat.name)))
+        .collect();
+    let list = List::new(strats)
+        .block(list_block("Strategies", app.focus() == Focus::Strategies))
+        .highlight_style(Style::default().fg(Color::Blue));
+    let mut state = ListState::default();
+    if !server.stategies.is_empty() {
+        state.select(Some(server.selected_strat));
+    }
+    f.render_stateful_widget(list, chunks[2], &mut state);
+
+    // Information about the selected database
+    let text: Vec<Spans> = server.db_info.iter()
+        .map(|line| Spans::from(Span::from(line.as_str())))
+        .collect();
+
+    let title = match server.databases.get(server.selected_db) {
+        Some(db) => db.name.as_str(),
+        None => "Information"
+    };
+    let block = Paragraph::new(text)
+        .block(make_block(title))
         .scroll((app.definition_scroll(), 0));
-    f.render_widget(block, rect);
+    f.render_widget(block, columns[1]);
 }
 
+fn draw_commands<B: Backend>(f: &mut Frame<B>, rect: Rect, app: &App) {
+    let mut lines: Vec<Spans> = Vec::new();
 
-fn draw_databases<B: Backend>(f: &mut Frame<B>, rect: Rect, app: &App) {
-    let dbs: Vec<Spans> = app.databases.iter()
-        .map(|db| {
-            Spans::from(vec![Span::from(format!("{} ({})", db.desc, db.name))])
-        }).collect();
+    for exchange in &app.commands {
+        let sent = app.source_label(exchange.server, &format!("> {}", exchange.line));
+        lines.push(Spans::from(Span::styled(sent, Style::default().fg(Color::Blue))));
 
-    let block = Paragraph::new(dbs)
-        .block(Block::default().borders(Borders::ALL))
+        for answer in &exchange.answers {
+            match answer {
+                RawAnswer::Reply(reply) => {
+                    lines.push(Spans::from(Span::from(reply.to_string())));
+                },
+                RawAnswer::Text(text) => {
+                    lines.extend(text.iter().map(|line| {
+                        Spans::from(Span::styled(format!("  {}", line), Style::default().fg(Color::Gray)))
+                    }));
+                }
+            }
+        }
+    }
+
+    let block = Paragraph::new(lines)
+        .block(make_block("Transcript"))
         .scroll((app.definition_scroll(), 0));
     f.render_widget(block, rect);
 }
 
-fn draw_strategies<B: Backend>(f: &mut Frame<B>, rect: Rect, app: &App) {
-    let dbs: Vec<Spans> = app.stategies.iter()
-        .map(|db| {
-            Spans::from(vec![Span::from(format!("{} ({})", db.desc, db.name))])
+fn draw_bookmarks<B: Backend>(f: &mut Frame<B>, rect: Rect, app: &App) {
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Percentage(40),
+                Constraint::Percentage(60),
+            ].as_ref()
+        ).split(rect);
+
+    let chunks = Layout::default()
+        .constraints(
+            [
+                Constraint::Percentage(30),
+                Constraint::Percentage(70),
+            ].as_ref()
+        ).split(columns[0]);
+
+    // Lists, the one bookmarks go to marked
+    let lists: Vec<ListItem> = app.bookmarks.lists.iter()
+        .map(|list| {
+            let mark = if list.name == app.word_list() { "*" } else { " " };
+            ListItem::new(format!("{} {} ({})", mark, list.name, list.entries.len()))
         }).collect();
+    let list = List::new(lists)
+        .block(list_block("Lists", app.focus() == Focus::WordLists))
+        .highlight_style(Style::default().fg(Color::Blue));
+    let mut state = ListState::default();
+    if !app.bookmarks.lists.is_empty() {
+        state.select(Some(app.selected_list()));
+    }
+    f.render_stateful_widget(list, chunks[0], &mut state);
 
-    let block = Paragraph::new(dbs)
-        .block(Block::default().borders(Borders::ALL))
+    // Words of the selected list
+    let entries: Vec<ListItem> = app.bookmarks.lists.get(app.selected_list())
+        .map(|list| list.entries.iter()
+            .map(|b| ListItem::new(format!("{} ({})", b.word, b.definition.source.name)))
+            .collect())
+        .unwrap_or_default();
+    let has_entries = !entries.is_empty();
+    let list = List::new(entries)
+        .block(list_block("Words", app.focus() == Focus::Bookmarks))
+        .highlight_style(Style::default().fg(Color::Blue));
+    let mut state = ListState::default();
+    if has_entries {
+        state.select(Some(app.selected_bookmark()));
+    }
+    f.render_stateful_widget(list, chunks[1], &mut state);
+
+    // Definition of the selected word
+    let text: Vec<Spans> = app.selected_entry()
+        .map(|b| b.definition.text.iter().map(|line| Spans::from(line.as_str())).collect())
+        .unwrap_or_default();
+    let block = Paragraph::new(text)
+        .block(make_block("Definition"))
         .scroll((app.definition_scroll(), 0));
-    f.render_widget(block, rect);
+    f.render_widget(block, columns[1]);
 }
 
-fn handle_key(key: &Key, app: &mut App) {
-    match (app.mode(), key) {
-        (AppMode::Define, Key::Ctrl('l')) => {
+// Roughly
+fn format_delay(secs: u64) -> String {
+    match secs {
+        0..=59 => String::from("less than a minute"),
+        60..=3599 => format!("{} minutes", secs / 60),
+        3600..=86399 => format!("{} hours", secs / 3600),
+        _ => format!("{} days", secs / 86400)
+    }
+}
+
+fn draw_quiz<B: Backend>(f: &mut Frame<B>, rect: Rect, app: &App) {
+    let chunks = Layout::default()
+        .constraints(
+            [
+                Constraint::Length(4),
+                Constraint::Min(0),
+            ].as_ref()
+        ).split(rect);
+
+    let review = match app.review() {
+        Some(review) => review,
+        None => return
+    };
+
+    let card = match app.card() {
+        Some(card) => card,
+        None => {
+            let text = match app.deck.next_due() {
+                None => String::from("Bookmark words with ALT-s to review them here"),
+                Some(due) => format!("Nothing left to review, {} reviewed. Next review in {}.",
+                    review.reviewed, format_delay(due.saturating_sub(quiz::now())))
+            };
+
+            let block = Paragraph::new(text)
+                .block(make_block("Quiz"));
+            f.render_widget(block, rect);
+            return;
+        }
+    };
+
+    let hint = if review.revealed {
+        "How well did you recall it? From 0 (not at all) to 5 (perfectly)"
+    } else {
+        "Press Enter or Space to reveal its definition"
+    };
+    let lines = vec![
+        Spans::from(Span::styled(card.word.as_str(), Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD))),
+        Spans::from(hint),
+    ];
+
+    let title = format!("Quiz ({} reviewed, {} left)", review.reviewed, review.left());
+    let block = Paragraph::new(lines)
+        .block(make_block(&title));
+    f.render_widget(block, chunks[0]);
+
+    // The answer
+    if review.revealed {
+        draw_definitions(f, chunks[1], app);
+    }
+}
+
+// A list under the search bar, from column
+fn draw_popup<B: Backend>(f: &mut Frame<B>, search: Rect, column: u16, items: &[&str], selected: Option<usize>) {
+    if items.is_empty() {
+        return;
+    }
+
+    let size = f.size();
+    let width = items.iter()
+        .map(|item| item.chars().count())
+        .max()
+        .unwrap_or(0) as u16 + 2;
+    let x = (search.x + 1 + column).min(size.width.saturating_sub(width));
+    let y = search.y + search.height;
+    let height = (items.len().min(8) as u16 + 2).min(size.height.saturating_sub(y));
+    let rect = Rect::new(x, y, width.min(size.width), height);
+
+    let items: Vec<ListItem> = items.iter().map(|item| ListItem::new(*item)).collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL))
+        .highlight_style(Style::default().fg(Color::Blue));
+    let mut state = ListState::default();
+    state.select(selected);
+
+    f.render_widget(Clear, rect);
+    f.render_stateful_widget(list, rect, &mut state);
+}
+
+fn draw_completion<B: Backend>(f: &mut Frame<B>, search: Rect, app: &App) {
+    if let Some(ref completion) = app.completion {
+        // Still looking for candidates
+        let items: Vec<&str> = if completion.query.is_some() {
+            vec!["..."]
+        } else {
+            completion.candidates.iter().map(String::as_str).collect()
+        };
+
+        draw_popup(f, search, app.searched.column(completion.start) as u16, &items, completion.selected);
+    } else if app.suggesting() {
+        let items: Vec<&str> = app.suggestions.words.iter().map(String::as_str).collect();
+
+        draw_popup(f, search, 0, &items, app.suggestions.selected);
+    }
+}
+
+// The keys of the mode, over the rest
+// What takes the keys before the mode, in the order the keys are handled
+fn layer(app: &App) -> Option<Layer> {
+    if app.history_search().is_some() {
+        Some(Layer::HistorySearch)
+    } else if app.find().is_some() {
+        Some(Layer::Find)
+    } else if app.focus() != Focus::SearchBar {
+        Some(Layer::List)
+    } else if app.suggesting() {
+        Some(Layer::Suggestions)
+    } else {
+        None
+    }
+}
+
+fn draw_help<B: Backend>(f: &mut Frame<B>, app: &App, keymap: &Keymap) {
+    let help = keymap.help(app.mode(), layer(app));
+    let width = help.iter().map(|(keys, _)| keys.chars().count()).max().unwrap_or(0);
+
+    let items: Vec<ListItem> = help.iter()
+        .map(|(keys, action)| {
+            // The name is the one of the configuration
+            let text = format!("{:width$}  {} ({})", keys, action.description(), action.name(), width = width);
+            ListItem::new(text)
+        })
+        .collect();
+
+    let size = f.size();
+    let height = (items.len() as u16 + 2).min(size.height);
+    let rect = Rect::new(size.width / 8, (size.height - height) / 2, size.width - size.width / 4, height);
+
+    let title = format!("Keys in {} mode", app.mode().text());
+    let list = List::new(items)
+        .block(make_block(&title));
+
+    f.render_widget(Clear, rect);
+    f.render_widget(list, rect);
+}
+
+// Returns true to quit
+fn run_action(action: Action, app: &mut App, keymap: &mut Keymap, help: &mut bool) -> bool {
+    match action {
+        Action::Quit => return true,
+        // Esc stops the running request first
+        Action::CancelOrQuit => return !app.cancel(),
+        Action::Cancel => {
+            app.cancel();
+        },
+        Action::Help => {
+            *help = true;
+        },
+        Action::Run => {
+            match app.mode() {
+                AppMode::Define => app.run_define(),
+                AppMode::Match => app.run_match(),
+                AppMode::Info => app.run_info(),
+                AppMode::Command => app.run_command(),
+                AppMode::Bookmarks => app.run_bookmarks(),
+                AppMode::Quiz => app.reveal()
+            }
+            // To move through what is found
+            keymap.set_vi_mode(ViMode::Normal);
+        },
+
+        // Scrolling
+        Action::ScrollUp => {
+            app.scroll_up();
+        },
+        Action::ScrollDown => {
+            app.scroll_down();
+        },
+        Action::LineUp => {
+            app.scroll_lines(-1);
+        },
+        Action::LineDown => {
+            app.scroll_lines(1);
+        },
+        Action::Top => {
+            app.scroll_top();
+        },
+        Action::Bottom => {
+            app.scroll_bottom();
+        },
+        Action::Find => {
+            app.start_find();
+        },
+
+        // Mode management
+        Action::Complete => {
+            if !app.complete(true) {
+                app.set_mode(app.mode().next());
+            }
+        },
+        Action::CompleteBackward => {
+            if !app.complete(false) {
+                app.set_mode(app.mode().previous());
+            }
+        },
+        Action::NextMode => {
+            app.set_mode(app.mode().next());
+        },
+        Action::PreviousMode => {
+            app.set_mode(app.mode().previous());
+        },
+
+        // Server management
+        Action::NextServer => {
+            app.next_server();
+        },
+        Action::PreviousServer => {
+            app.previous_server();
+        },
+        Action::AllServers => {
+            app.toggle_all_servers();
+        },
+
+        // Lists and definitions
+        Action::Focus => {
+            app.next_focus();
+        },
+        Action::SelectNext => {
+            app.select(1);
+        },
+        Action::SelectPrevious => {
+            app.select(-1);
+        },
+        Action::Accept => {
+            app.pick();
+        },
+        Action::DeleteBookmark => {
+            app.delete_bookmark();
+        },
+        Action::Back => {
+            app.back();
+        },
+        Action::Forward => {
+            app.forward();
+        },
+        Action::NextDefinition => {
             app.next_definition();
         },
-        (AppMode::Define, Key::Ctrl('h')) => {
+        Action::PreviousDefinition => {
             app.previous_definition();
         },
+        Action::NextDatabase => {
+            app.next_database();
+        },
+        Action::PreviousDatabase => {
+            app.previous_database();
+        },
+
+        // Bookmarks
+        Action::Bookmark => {
+            app.bookmark();
+        },
+        Action::Export => {
+            app.export_bookmarks();
+        },
+        Action::Reveal => {
+            app.reveal();
+        },
+        Action::Grade(quality) => {
+            app.grade(quality);
+        },
+
+        // History management
+        Action::SearchHistory => {
+            app.search_history();
+        },
+        Action::HistoryPrevious => {
+            app.history_goto(HistoryMovement::Previous);
+        },
+        Action::HistoryNext => {
+            app.history_goto(HistoryMovement::Next);
+        },
+
+        // Search management
+        Action::DeleteBackward => {
+            app.searched.delete()
+        },
+        Action::ClearSearch => {
+            app.searched.clear()
+        },
+        Action::CursorLeft => {
+            app.searched.move_cursor(CursorDirection::Left)
+        },
+        Action::CursorRight => {
+            app.searched.move_cursor(CursorDirection::Right)
+        },
+        Action::CursorStart => {
+            app.searched.move_cursor(CursorDirection::First)
+        },
+        Action::CursorEnd => {
+            app.searched.move_cursor(CursorDirection::Last)
+        },
+        Action::WordLeft => {
+            app.searched.move_cursor(CursorDirection::WordLeft)
+        },
+        Action::WordRight => {
+            app.searched.move_cursor(CursorDirection::WordRight)
+        },
+        Action::DeleteForward => {
+            app.searched.delete_forward()
+        },
+        Action::KillToEnd => {
+            app.searched.kill_to_end()
+        },
+        Action::KillWordBackward => {
+            app.searched.kill_word_backward()
+        },
+        Action::Yank => {
+            app.searched.yank()
+        },
+        Action::YankPop => {
+            app.searched.yank_pop()
+        },
+        Action::Transpose => {
+            app.searched.transpose()
+        },
+        Action::Undo => {
+            app.searched.undo()
+        },
+        Action::Redo => {
+            app.searched.redo()
+        },
 
-        // Enter mappings
-        (AppMode::Define, Key::Char('\n')) => {
-            app.run_define();
+        // Vi's modes
+        Action::NormalMode => {
+            keymap.set_vi_mode(ViMode::Normal);
         },
-        (AppMode::Databases, Key::Char('\n')) => {
-            app.run_show_dbs();
+        Action::Insert => {
+            keymap.set_vi_mode(ViMode::Insert);
         },
-        (AppMode::Strategies, Key::Char('\n')) => {
-            app.run_show_strats();
+        Action::Append => {
+            app.searched.move_cursor(CursorDirection::Right);
+            keymap.set_vi_mode(ViMode::Insert);
         },
-        (AppMode::Match, Key::Char('\n')) => {
-            app.run_match();
+        Action::InsertStart => {
+            app.searched.move_cursor(CursorDirection::First);
+            keymap.set_vi_mode(ViMode::Insert);
+        },
+        Action::AppendEnd => {
+            app.searched.move_cursor(CursorDirection::Last);
+            keymap.set_vi_mode(ViMode::Insert);
         }
-        _ => {}
     }
+
+    false
 }
 
 fn main() -> Result<(), io::Error> {
 
-    let validate_url = |url: String| -> Result<(), String> {
-        DICTUrl::new(&url).map_err(|e| e.to_string())?;
+    // Mirrors of a server are separated by commas
+    let validate_url = |urls: String| -> Result<(), String> {
+        for url in urls.split(',') {
+            DICTUrl::new(url).map_err(|e| e.to_string())?;
+        }
         Ok(())
     };
 
+    let validate_size = |size: String| -> Result<(), String> {
+        size.parse::<usize>().map(|_| ()).map_err(|e| e.to_string())
+    };
+
     let matches = clap_app!(redict =>
         (version: crate_version!())
         (author: crate_authors!())
         (about: "Connect and navigate DICT servers")
-        (@arg SERVER: +required {validate_url} "Url to connect to")
+        (@arg SERVER: +multiple {validate_url} "Urls of the servers to connect to, each followed by its mirrors after commas. Those of the configuration by default")
+        (@arg CONFIG: --config +takes_value "Reads the configuration from this file")
+        (@arg RECORD: --record +takes_value "Records a transcript of the session in this file")
+        (@arg CLIENT: --client +takes_value "Name sent to the servers")
+        (@arg DATABASE: --database +takes_value "Database searched when none is given in the search bar")
+        (@arg STRATEGY: --strategy +takes_value "Strategy used when none is given in the search bar")
+        (@arg HISTORY_SIZE: --("history-size") +takes_value {validate_size} "Number of searches kept in the history file")
+        (@arg OFFLINE: --offline "Shows only the definitions in the cache, however old")
+        (@arg VI: --vi "Moves with the keys of vi, in its normal mode, and types in its insert mode")
     ).get_matches();
 
-    let url = matches.value_of("SERVER").unwrap();
+    let config_path = matches.value_of("CONFIG").map(PathBuf::from).or_else(config::default_path);
+    let mut config = match config_path {
+        Some(path) => Config::load(&path).unwrap_or_else(|e| {
+            let message = format!("Invalid configuration in {}: {}", path.display(), e);
+            clap::Error::with_description(&message, clap::ErrorKind::InvalidValue).exit()
+        }),
+        None => Config::default()
+    };
 
-    let mut stdin = std::io::stdin().keys();
+    // The command line comes first
+    if let Some(urls) = matches.values_of("SERVER") {
+        config.servers = urls.map(String::from).collect();
+    }
+    if let Some(client) = matches.value_of("CLIENT") {
+        config.client = client.to_owned();
+    }
+    if let Some(db) = matches.value_of("DATABASE") {
+        config.database = db.to_owned();
+    }
+    if let Some(strat) = matches.value_of("STRATEGY") {
+        config.strategy = strat.to_owned();
+    }
+    if let Ok(size) = value_t!(matches, "HISTORY_SIZE", usize) {
+        config.history_size = size;
+    }
+    if matches.is_present("VI") {
+        config.vi = true;
+    }
+
+    if config.servers.is_empty() {
+        let message = "No server given, on the command line or in the configuration";
+        clap::Error::with_description(message, clap::ErrorKind::MissingRequiredArgument).exit();
+    }
+    for urls in &config.servers {
+        if let Err(e) = validate_url(urls.clone()) {
+            let message = format!("Invalid server {} in the configuration: {}", urls, e);
+            clap::Error::with_description(&message, clap::ErrorKind::InvalidValue).exit();
+        }
+    }
+
+    // One transcript per server
+    let recorders = match matches.value_of("RECORD") {
+        Some(path) => (0..config.servers.len()).map(|i| {
+            let path = if i == 0 { path.to_owned() } else { format!("{}.{}", path, i) };
+            Recorder::create(&path).unwrap_or_else(|e| {
+                let message = format!("Could not create the transcript {}: {}", path, e);
+                clap::Error::with_description(&message, clap::ErrorKind::InvalidValue).exit()
+            })
+        }).collect(),
+        None => Vec::new()
+    };
+
+    let history = match history::default_path() {
+        Some(path) => History::load(path, config.history_size),
+        None => History::new(config.history_size)
+    };
+    let bookmarks = match bookmarks::default_dir() {
+        Some(dir) => Bookmarks::load(dir),
+        None => Bookmarks::new()
+    };
+    let deck = match quiz::default_path() {
+        Some(path) => Deck::load(path),
+        None => Deck::new()
+    };
+
+    let max_age = Some(config.cache.days.saturating_mul(24 * 60 * 60));
+    let mut cache = match cache::default_dir() {
+        Some(dir) => Cache::open(dir, config.cache.size, max_age),
+        None => Cache::new(config.cache.size, max_age)
+    };
+    if matches.is_present("OFFLINE") {
+        cache = cache.offline();
+    }
+
+    let mut keymap = Keymap::new(&config.keys).unwrap_or_else(|e| {
+        let message = format!("Invalid keys in the configuration: {}", e);
+        clap::Error::with_description(&message, clap::ErrorKind::InvalidValue).exit()
+    });
+    if config.vi {
+        keymap = keymap.vi();
+    }
+
+    let events = Events::new();
 
     // First answer
-    let mut app = App::new(url);
+    let mut app = App::new(config, recorders, history, bookmarks, deck, cache, events.sender());
 
     let stdout = io::stdout().into_raw_mode()?;
     let stdout = AlternateScreen::from(stdout);
     let backend = TermionBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    loop {
+    let mut help = false;
+
+    'main: loop {
+        app.tick();
+
         terminal.draw(|f| {
 
             // Panes
@@ -164,102 +783,277 @@ fn main() -> Result<(), io::Error> {
 
 
             // Search bar
-            app.searched.draw(f, chunks[0], make_block("Search"));
+            let title = match (app.history_search(), app.find()) {
+                (Some(search), _) if search.found.is_none() && !search.query.is_empty() => {
+                    format!("Search (failing reverse-i-search: {})", search.query)
+                },
+                (Some(search), _) => format!("Search (reverse-i-search: {})", search.query),
+                (None, Some(query)) => format!("Search (/{})", query),
+                (None, None) => String::from("Search")
+            };
+            app.searched.draw(f, chunks[0], make_block(&title));
 
             // Status section
-            let block = Paragraph::new(
-                if let Some(ref reply) = app.last_status {
-                    reply.to_string()
-                } else {
-                    String::from("No status")
-                }
-                )
-                .block(make_block("Status"));
+            // With the count and the start of a key sequence
+            let mut typed: Vec<String> = keymap.count().map(|count| count.to_string()).into_iter().collect();
+            if !keymap.pending().is_empty() {
+                typed.push(keymap::keys_name(keymap.pending()));
+            }
+            let title = match typed.len() {
+                0 => String::from("Status"),
+                _ => format!("Status ({} -)", typed.join(" "))
+            };
+            let block = Paragraph::new(app.status_text())
+                .block(make_block(&title));
             f.render_widget(block, chunks[1]);
 
-            // Mode display
+            // Mode and server display
+            let tab_chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(
+                    [
+                        Constraint::Percentage(60),
+                        Constraint::Percentage(40),
+                    ].as_ref()
+                )
+                .split(chunks[2]);
+
             let titles = AppMode::values().iter()
                 .map(|mode| {
                     Spans::from(vec![Span::from(mode.text())])
                 }).collect();
+            // Preceded by vi's mode, if used
+            let modes_rect = match keymap.vi_mode() {
+                Some(vi) => {
+                    let parts = Layout::default()
+                        .direction(Direction::Horizontal)
+                        .constraints(
+                            [
+                                Constraint::Length(8),
+                                Constraint::Min(0),
+                            ].as_ref()
+                        )
+                        .split(tab_chunks[0]);
+
+                    let label = Span::styled(format!(" {}", vi.text()), Style::default().add_modifier(Modifier::BOLD));
+                    f.render_widget(Paragraph::new(label), parts[0]);
+                    parts[1]
+                },
+                None => tab_chunks[0]
+            };
+
             let modes = Tabs::new(titles)
                 .highlight_style(Style::default().fg(Color::Blue))
                 .select(app.mode().into());
-            f.render_widget(modes, chunks[2]);
+            f.render_widget(modes, modes_rect);
+
+            // When querying all servers, all of them are highlighted
+            let titles = app.servers.iter()
+                .map(|server| {
+                    if app.all_servers() {
+                        Spans::from(Span::styled(&server.name, Style::default().fg(Color::Blue)))
+                    } else {
+                        Spans::from(Span::from(&*server.name))
+                    }
+                }).collect();
+            let servers = Tabs::new(titles)
+                .highlight_style(Style::default().fg(Color::Blue))
+                .select(app.current_server());
+            f.render_widget(servers, tab_chunks[1]);
 
             match app.mode() {
                 AppMode::Define => {
                     draw_definitions(f, chunks[3], &app);
                 },
-                AppMode::Databases => {
-                    draw_databases(f, chunks[3], &app);
+                AppMode::Info => {
+                    draw_info(f, chunks[3], &app);
                 },
-                AppMode::Strategies => {
-                    draw_strategies(f, chunks[3], &app);
-                }
                 AppMode::Match => {
                     draw_matches(f, chunks[3], &app);
+                },
+                AppMode::Command => {
+                    draw_commands(f, chunks[3], &app);
+                },
+                AppMode::Bookmarks => {
+                    draw_bookmarks(f, chunks[3], &app);
+                },
+                AppMode::Quiz => {
+                    draw_quiz(f, chunks[3], &app);
                 }
             }
 
+            draw_completion(f, chunks[0], &app);
+
+            if help {
+                draw_help(f, &app, &keymap);
+            }
+
         })?;
 
-        if let Some(Ok(evt)) = stdin.next() {
-            match evt {
-                Key::Esc | Key::Ctrl('c') | Key::Ctrl('q') => { break ;},
+        let key = match events.next(app.timeout()) {
+            Some(Event::Key(key)) => key,
+            Some(Event::Alt(key)) => {
+                for step in keymap.feed(app.mode(), KeyPress::Alt(key)) {
+                    if let Step::Action(action) = step {
+                        if run_action(action, &mut app, &mut keymap, &mut help) {
+                            break 'main;
+                        }
+                    }
+                }
+                continue;
+            },
+            Some(Event::Answer(server, id, outcome)) => {
+                app.answered(server, id, outcome);
+                continue;
+            },
+            Some(Event::Done(server, id)) => {
+                app.done(server, id);
+                continue;
+            },
+            Some(Event::Completions(query, words)) => {
+                app.completions_found(query, words);
+                continue;
+            },
+            Some(Event::Suggestions(query, words)) => {
+                app.suggestions.found(query, words);
+                continue;
+            },
+            Some(Event::Tick) => continue,
+            Some(Event::End) | None => break
+        };
+
+        // Any key closes the help
+        if help {
+            help = false;
+            continue;
+        }
+
+        // Any other key than those completing ends the completion, Esc
+        // only does that
+        let completing = keymap.does(app.mode(), KeyPress::Key(key), Action::Complete)
+            || keymap.does(app.mode(), KeyPress::Key(key), Action::CompleteBackward);
+        if !completing && app.completion.is_some() {
+            app.close_completion();
+            if key == Key::Esc {
+                continue;
+            }
+        }
 
-                // Scrolling
-                Key::PageUp => {
-                    app.scroll_up();
+        // The history search takes the keys that edit it, others end it
+        if app.history_search().is_some() {
+            match keymap.layer_action(Layer::HistorySearch, KeyPress::Key(key)) {
+                Some(Action::SearchHistory) => {
+                    app.search_history();
+                    continue;
                 },
-                Key::PageDown => {
-                    app.scroll_down();
+                Some(Action::DeleteBackward) => {
+                    app.history_search_delete();
+                    continue;
                 },
-
-                // Mode management
-                Key::Char('\t') => {
-                    app.set_mode(app.mode().next());
+                Some(Action::Cancel) => {
+                    app.cancel_history_search();
+                    continue;
                 },
-                Key::BackTab => {
-                    app.set_mode(app.mode().previous());
+                _ => match key {
+                    Key::Char(c) if c != '\n' && c != '\t' => {
+                        app.history_search_edit(c);
+                        continue;
+                    },
+                    _ => app.accept_history_search()
                 }
+            }
+        }
 
-                // Search management
-                Key::Char(c) if c != '\n' => {
-                    app.searched.edit(c)
-                },
-                Key::Backspace => {
-                    app.searched.delete()
+        // The search in the text takes the keys typed, until Enter or Esc
+        if app.find().is_some() {
+            match keymap.layer_action(Layer::Find, KeyPress::Key(key)) {
+                Some(Action::Accept) => app.accept_find(),
+                Some(Action::DeleteBackward) => app.find_delete(),
+                Some(Action::Cancel) => app.cancel_find(),
+                _ => if let Key::Char(c) = key {
+                    app.find_edit(c);
+                }
+            }
+            continue;
+        }
+
+        // A focused list takes the moves and Enter, Esc leaves it
+        if app.focus() != Focus::SearchBar {
+            match keymap.layer_action(Layer::List, KeyPress::Key(key)) {
+                Some(Action::SelectNext) => {
+                    app.select(1);
+                    continue;
                 },
-                Key::Ctrl('u') => {
-                    app.searched.clear()
+                Some(Action::SelectPrevious) => {
+                    app.select(-1);
+                    continue;
                 },
-                Key::Left => {
-                    app.searched.move_cursor(CursorDirection::Left)
+                Some(Action::Accept) => {
+                    app.pick();
+                    continue;
                 },
-                Key::Right => {
-                    app.searched.move_cursor(CursorDirection::Right)
+                Some(Action::Cancel) => {
+                    app.unfocus();
+                    continue;
                 },
-                Key::Ctrl('b') | Key::Home => {
-                    app.searched.move_cursor(CursorDirection::First)
+                Some(Action::DeleteBookmark) if app.focus() == Focus::Bookmarks => {
+                    app.delete_bookmark();
+                    continue;
                 },
-                Key::Ctrl('e') | Key::End => {
-                    app.searched.move_cursor(CursorDirection::Last)
-                }
+                _ => {}
+            }
+        }
 
-                // History management
-                Key::Down => {
-                    app.history_goto(HistoryMovement::Previous);
+        // The suggestions take the arrows and Enter while shown
+        if app.suggesting() {
+            match keymap.layer_action(Layer::Suggestions, KeyPress::Key(key)) {
+                Some(Action::Cancel) => {
+                    app.suggestions.close();
+                    continue;
                 },
-                Key::Up => {
-                    app.history_goto(HistoryMovement::Next);
-                }
+                Some(Action::SelectNext) => {
+                    app.suggestions.select(true);
+                    continue;
+                },
+                Some(Action::SelectPrevious) => {
+                    app.suggestions.select(false);
+                    continue;
+                },
+                Some(Action::Accept) if app.suggestions.selected.is_some() => {
+                    app.pick_suggestion();
+                    continue;
+                },
+                _ => {}
+            }
+        }
+
+        // Characters typed go to the search bar, rather than to the keys
+        // they are bound to. With vi's modes, they do in the insert one.
+        let typing = match keymap.vi_mode() {
+            Some(ViMode::Normal) => false,
+            Some(ViMode::Insert) | None => app.typing()
+        };
+        match key {
+            Key::Char(c) if typing && keymap.pending().is_empty() && c != '\n' && c != '\t' => {
+                app.searched.edit(c);
+                continue;
+            },
+            _ => {}
+        }
 
-                // Other keys depend on the mode
-                key => handle_key(&key, &mut app)
+        for step in keymap.feed(app.mode(), KeyPress::Key(key)) {
+            match step {
+                Step::Action(action) => {
+                    if run_action(action, &mut app, &mut keymap, &mut help) {
+                        break 'main;
+                    }
+                },
+                Step::Key(KeyPress::Key(Key::Char(c)))
+                    if c != '\n' && app.mode() != AppMode::Quiz && keymap.vi_mode() != Some(ViMode::Normal) => {
+                    app.searched.edit(c);
+                },
+                Step::Key(_) => {}
             }
-        } else {
-            break;
         }
     }
 
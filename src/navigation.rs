@@ -0,0 +1,98 @@
+/// Pages visited, to go back and forth between them like a browser
+pub struct Navigation<T> {
+    pages: Vec<T>,
+    current: usize,
+    max: usize
+}
+
+impl<T> Navigation<T> {
+    pub fn new(max: usize) -> Self {
+        Navigation {
+            pages: Vec::new(),
+            current: 0,
+            max
+        }
+    }
+
+    /// Visits a new page, forgetting those gone back from
+    pub fn visit(&mut self, page: T) {
+        if !self.pages.is_empty() {
+            self.pages.truncate(self.current + 1);
+        }
+
+        self.pages.push(page);
+        if self.pages.len() > self.max {
+            self.pages.remove(0);
+        }
+
+        self.current = self.pages.len() - 1;
+    }
+
+    pub fn current_mut(&mut self) -> Option<&mut T> {
+        self.pages.get_mut(self.current)
+    }
+
+    pub fn back(&mut self) -> Option<&T> {
+        if self.current == 0 {
+            return None;
+        }
+
+        self.current -= 1;
+        self.pages.get(self.current)
+    }
+
+    pub fn forward(&mut self) -> Option<&T> {
+        if self.current + 1 >= self.pages.len() {
+            return None;
+        }
+
+        self.current += 1;
+        self.pages.get(self.current)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn back_and_forth() {
+        let mut nav = Navigation::new(10);
+        assert_eq!(nav.back(), None);
+
+        nav.visit("tie");
+        nav.visit("tin");
+        nav.visit("tip");
+
+        assert_eq!(nav.back(), Some(&"tin"));
+        assert_eq!(nav.back(), Some(&"tie"));
+        assert_eq!(nav.back(), None);
+        assert_eq!(nav.forward(), Some(&"tin"));
+        assert_eq!(nav.forward(), Some(&"tip"));
+        assert_eq!(nav.forward(), None);
+    }
+
+    #[test]
+    fn visit_drops_forward() {
+        let mut nav = Navigation::new(10);
+        nav.visit("tie");
+        nav.visit("tin");
+        nav.back();
+
+        nav.visit("tip");
+        assert_eq!(nav.forward(), None);
+        assert_eq!(nav.back(), Some(&"tie"));
+    }
+
+    #[test]
+    fn oldest_dropped() {
+        let mut nav = Navigation::new(2);
+        nav.visit("tie");
+        nav.visit("tin");
+        nav.visit("tip");
+
+        assert_eq!(nav.current_mut(), Some(&mut "tip"));
+        assert_eq!(nav.back(), Some(&"tin"));
+        assert_eq!(nav.back(), None);
+    }
+}
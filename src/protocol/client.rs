@@ -0,0 +1,374 @@
+//! A `DICTConnection` that comes back by itself when the server drops it,
+//! possibly on a mirror.
+use super::{Database, Definition, Match, Strategy};
+use crate::connection::{Banner, CancelHandle, DICTConnection, DICTError, RawAnswer, Timeouts};
+use crate::reply::{ParseReplyError, Reply};
+use crate::status::{Category, ReplyKind, Status};
+use crate::transcript::Recorder;
+use std::io::ErrorKind;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often a cancellation is checked while waiting to connect again.
+const PAUSE_STEP: Duration = Duration::from_millis(20);
+
+/// How to wait between connection attempts: the delay starts at `initial`,
+/// doubles after each failure up to `max`, and gives up after `attempts`.
+#[derive(Debug, Clone, Copy)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    pub attempts: usize,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            initial: Duration::from_millis(200),
+            max: Duration::from_secs(5),
+            attempts: 5,
+        }
+    }
+}
+
+/// Whether `err` means that the server is gone, and that the command may
+/// succeed on a new connection.
+pub fn is_disconnection(err: &DICTError) -> bool {
+    match err {
+        DICTError::ReplyError(ParseReplyError::FailedToRead) => true,
+        DICTError::ReadWriteError(e) => matches!(
+            e.kind(),
+            ErrorKind::BrokenPipe
+                | ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::NotConnected
+                | ErrorKind::UnexpectedEof
+        ),
+        DICTError::SystemError(r) => {
+            r.status == Status(ReplyKind::NegativeTransient, Category::Connection, 1)
+        }
+        _ => false,
+    }
+}
+
+/// Whether the server asked to be left alone for now (420 and 421): an
+/// equivalent server should be tried instead.
+fn is_unavailable(err: &DICTError) -> bool {
+    match err {
+        DICTError::SystemError(r) => {
+            r.status.0 == ReplyKind::NegativeTransient
+                && r.status.1 == Category::Connection
+                && (r.status.2 == 0 || r.status.2 == 1)
+        }
+        _ => false,
+    }
+}
+
+/// Aborts what a client is doing, from any thread, even while it waits or
+/// tries to connect again.
+#[derive(Clone)]
+pub struct ClientCancelHandle {
+    cancelled: Arc<AtomicBool>,
+    conn: Arc<Mutex<Option<CancelHandle>>>,
+}
+
+impl ClientCancelHandle {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+
+        if let Some(ref handle) = *self.conn.lock().unwrap() {
+            handle.cancel();
+        }
+    }
+}
+
+pub struct DICTClient {
+    /// The server then its mirrors, in the order they are tried.
+    servers: Vec<(String, u16)>,
+    active: usize,
+    client: String,
+    auth: Option<(String, String)>,
+    timeouts: Timeouts,
+    backoff: Backoff,
+    recorder: Option<Recorder>,
+
+    conn: Option<DICTConnection>,
+    banner: Banner,
+    reconnects: usize,
+    cancelled: Arc<AtomicBool>,
+    // The handle of the current connection
+    conn_cancel: Arc<Mutex<Option<CancelHandle>>>,
+}
+
+impl DICTClient {
+    /// Does not connect yet, see `connect`.
+    pub fn new(host: &str, port: u16) -> Self {
+        DICTClient {
+            servers: vec![(host.to_owned(), port)],
+            active: 0,
+            client: String::from("redict"),
+            auth: None,
+            timeouts: Timeouts::default(),
+            backoff: Backoff::default(),
+            recorder: None,
+            conn: None,
+            banner: Banner::default(),
+            reconnects: 0,
+            cancelled: Arc::new(AtomicBool::new(false)),
+            conn_cancel: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Adds an equivalent server, tried when the previous ones are down.
+    pub fn mirror(mut self, host: &str, port: u16) -> Self {
+        self.servers.push((host.to_owned(), port));
+        self
+    }
+
+    /// The server used by the current session, or by the next one.
+    pub fn active_server(&self) -> (&str, u16) {
+        let (ref host, port) = self.servers[self.active];
+        (host, port)
+    }
+
+    /// The name sent with `CLIENT` on each connection.
+    pub fn client_name(mut self, client: &str) -> Self {
+        self.client = client.to_owned();
+        self
+    }
+
+    pub fn auth(mut self, user: &str, secret: &str) -> Self {
+        self.auth = Some((user.to_owned(), secret.to_owned()));
+        self
+    }
+
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Records every connection in the same transcript.
+    pub fn recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    pub fn msg_id(&self) -> &str {
+        &self.banner.msg_id
+    }
+
+    /// Those announced by the server of the current session.
+    pub fn capabilities(&self) -> &[String] {
+        &self.banner.capabilities
+    }
+
+    /// How many connection attempts the last command needed. A successful
+    /// `connect` leaves it at zero: it is not a reconnection.
+    pub fn reconnect_attempts(&self) -> usize {
+        self.reconnects
+    }
+
+    /// Cancels the commands run from now on, until another handle is made.
+    pub fn cancel_handle(&mut self) -> ClientCancelHandle {
+        self.cancelled = Arc::new(AtomicBool::new(false));
+
+        ClientCancelHandle {
+            cancelled: Arc::clone(&self.cancelled),
+            conn: Arc::clone(&self.conn_cancel),
+        }
+    }
+
+    fn check_cancelled(&self) -> Result<(), DICTError> {
+        if self.cancelled.load(Ordering::SeqCst) {
+            Err(DICTError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Sleeps for `delay`, but not after a cancellation.
+    fn pause(&self, delay: Duration) -> Result<(), DICTError> {
+        let end = Instant::now() + delay;
+
+        loop {
+            self.check_cancelled()?;
+
+            let remaining = end.saturating_duration_since(Instant::now());
+            if remaining == Duration::from_secs(0) {
+                return Ok(());
+            }
+            thread::sleep(std::cmp::min(remaining, PAUSE_STEP));
+        }
+    }
+
+    /// Tries each server once, starting from the active one, and keeps the
+    /// first one that answers. Returns its banner.
+    fn open(&mut self) -> Result<Reply, DICTError> {
+        self.conn = None;
+        *self.conn_cancel.lock().unwrap() = None;
+        let mut last_err = None;
+
+        for i in 0..self.servers.len() {
+            let index = (self.active + i) % self.servers.len();
+            self.check_cancelled()?;
+
+            match self.open_on(index) {
+                Ok(banner) => {
+                    self.active = index;
+                    return Ok(banner);
+                }
+                Err(e) if is_refusal(&e) => return Err(e),
+                Err(e) => last_err = Some(e),
+            }
+        }
+
+        Err(last_err.unwrap())
+    }
+
+    /// Opens a session on one server: connects, reads the banner, then sends
+    /// `CLIENT` and `AUTH` if configured.
+    fn open_on(&mut self, index: usize) -> Result<Reply, DICTError> {
+        let (ref host, port) = self.servers[index];
+        let stream = self.timeouts.connect((host.as_str(), port))?;
+        let mut conn = match self.recorder {
+            Some(ref recorder) => DICTConnection::recorded(stream, recorder)?,
+            None => DICTConnection::new(stream)?,
+        };
+        conn.set_timeouts(self.timeouts)?;
+
+        // Checked once the handle is there, not to miss a cancellation
+        // made meanwhile
+        *self.conn_cancel.lock().unwrap() = Some(conn.cancel_handle());
+        self.check_cancelled()?;
+
+        let (banner, reply) = conn.start()?;
+        conn.client(self.client.clone())?;
+        if let Some((ref user, ref secret)) = self.auth {
+            conn.auth(user, secret, &banner.msg_id)?;
+        }
+
+        self.banner = banner;
+        self.conn = Some(conn);
+        Ok(reply)
+    }
+
+    /// Opens a session, retrying as told by the backoff.
+    pub fn connect(&mut self) -> Result<Reply, DICTError> {
+        self.reconnects = 0;
+        let banner = self.reconnect()?;
+        self.reconnects = 0;
+        Ok(banner)
+    }
+
+    fn reconnect(&mut self) -> Result<Reply, DICTError> {
+        let mut delay = self.backoff.initial;
+
+        for attempt in 1.. {
+            self.check_cancelled()?;
+            self.reconnects += 1;
+
+            match self.open() {
+                Ok(banner) => return Ok(banner),
+                Err(e) if attempt >= self.backoff.attempts || is_refusal(&e) => return Err(e),
+                Err(_) => {
+                    self.pause(delay)?;
+                    delay = std::cmp::min(delay * 2, self.backoff.max);
+                }
+            }
+        }
+
+        unreachable!()
+    }
+
+    /// Runs `f`, reconnecting and running it again once if the server went
+    /// away meanwhile. Only meant for idempotent commands.
+    fn retry<T, F>(&mut self, mut f: F) -> Result<T, DICTError>
+    where
+        F: FnMut(&mut DICTConnection) -> Result<T, DICTError>,
+    {
+        self.reconnects = 0;
+        let mut retried = false;
+
+        loop {
+            if !self.conn.as_ref().map_or(false, DICTConnection::is_usable) {
+                self.reconnect()?;
+            }
+
+            match f(self.conn.as_mut().unwrap()) {
+                Err(e) if !retried && (is_disconnection(&e) || is_unavailable(&e)) => {
+                    if is_unavailable(&e) {
+                        self.active = (self.active + 1) % self.servers.len();
+                    }
+                    self.conn = None;
+                    retried = true;
+                }
+                ret => return ret,
+            }
+        }
+    }
+
+    pub fn define(
+        &mut self,
+        database: Database,
+        word: String,
+    ) -> Result<(Vec<Definition>, Reply), DICTError> {
+        self.retry(|conn| conn.define(database.clone(), word.clone()))
+    }
+
+    pub fn match_db(
+        &mut self,
+        db: Database,
+        strat: Strategy,
+        word: String,
+    ) -> Result<(Vec<Match>, Reply), DICTError> {
+        self.retry(|conn| conn.match_db(db.clone(), strat.clone(), word.clone()))
+    }
+
+    pub fn show_db(&mut self) -> Result<(Vec<Database>, Reply), DICTError> {
+        self.retry(DICTConnection::show_db)
+    }
+
+    pub fn show_strat(&mut self) -> Result<(Vec<Strategy>, Reply), DICTError> {
+        self.retry(DICTConnection::show_strat)
+    }
+
+    pub fn show_info(&mut self, db: Database) -> Result<(Vec<String>, Reply), DICTError> {
+        self.retry(|conn| conn.show_info(db.clone()))
+    }
+
+    pub fn show_server(&mut self) -> Result<(Vec<String>, Reply), DICTError> {
+        self.retry(DICTConnection::show_server)
+    }
+
+    pub fn status(&mut self) -> Result<Reply, DICTError> {
+        self.retry(DICTConnection::status)
+    }
+
+    /// Sends `line` as is. It is not sent again if the server goes away
+    /// meanwhile, as it may not be idempotent.
+    pub fn raw_command(&mut self, line: &str) -> Result<Vec<RawAnswer>, DICTError> {
+        self.reconnects = 0;
+
+        if !self.conn.as_ref().map_or(false, DICTConnection::is_usable) {
+            self.reconnect()?;
+        }
+
+        self.conn.as_mut().unwrap().raw_command(line)
+    }
+}
+
+/// Whether the server explicitly refused the session, for instance because
+/// authentication failed: trying again won't help.
+fn is_refusal(err: &DICTError) -> bool {
+    match err {
+        DICTError::SystemError(r) => r.status.0 == ReplyKind::NegativePermanent,
+        _ => false,
+    }
+}
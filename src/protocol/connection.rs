@@ -1,10 +1,16 @@
 use super::{Database, Definition, Match, Strategy};
+use crate::machine::{Command, Event, Machine, Response};
 use crate::reply::{ParseReplyError, Reply};
-use crate::status::{Category, ReplyKind, Status};
+use crate::transcript::{Recorder, Transcript};
 use std::convert::From;
-use std::io::{BufRead, BufReader, BufWriter, Write};
-use std::net::TcpStream;
+use std::error::Error;
+use std::fmt::Display;
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
 use std::ops::Drop;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 #[derive(Debug)]
 pub enum DICTError {
@@ -16,8 +22,26 @@ pub enum DICTError {
     NoAnswer,
     ReadWriteError(std::io::Error),
     MalformedAnswer(&'static str),
+    Timeout,
+    Cancelled,
 }
 
+impl Display for DICTError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ReplyError(e) => write!(f, "Invalid reply: {}", e),
+            Self::SystemError(r) | Self::UnexpectedPacket(DICTPacket(_, r)) => write!(f, "{}", r),
+            Self::NoAnswer => write!(f, "No answer"),
+            Self::ReadWriteError(e) => write!(f, "{}", e),
+            Self::MalformedAnswer(what) => write!(f, "Malformed answer: {}", what),
+            Self::Timeout => write!(f, "Timed out"),
+            Self::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+impl Error for DICTError {}
+
 impl From<ParseReplyError> for DICTError {
     fn from(src: ParseReplyError) -> Self {
         DICTError::ReplyError(src)
@@ -32,14 +56,20 @@ impl From<std::io::Error> for DICTError {
 
 pub type DICTResult<T> = Result<(T, Reply), DICTError>;
 
-#[derive(Debug)]
+/// Part of the answer to a raw command, in the order it was received.
+#[derive(Debug, Clone)]
+pub enum RawAnswer {
+    Reply(Reply),
+    Text(Vec<String>),
+}
+
+#[derive(Debug, Clone)]
 pub enum DICTPacketKind {
     // Generic
     ReplyOnly,
     OkReply,
 
-    // TODO: parse capabilities too
-    InitialConnection(String),
+    InitialConnection(Banner),
 
     // DEFINE packets
     DefinitionsFollow,
@@ -51,58 +81,327 @@ pub enum DICTPacketKind {
     // SHOW packets
     Databases(Vec<Database>),
     Strategies(Vec<Strategy>), // TODO: There is way more specific packets
+
+    // Any other text block
+    Text(Vec<String>),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DICTPacket(pub DICTPacketKind, pub Reply);
 
+/// What the server tells about itself when the connection opens.
+#[derive(Debug, Clone, Default)]
+pub struct Banner {
+    /// Needed by `auth`.
+    pub msg_id: String,
+    /// Extensions supported by the server, like `auth` or `mime`.
+    pub capabilities: Vec<String>,
+}
+
+/// Limits on how long a connection may wait. `None` means forever.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timeouts {
+    pub connect: Option<Duration>,
+    pub read: Option<Duration>,
+    pub write: Option<Duration>,
+    /// For a command to be sent and completely answered.
+    pub command: Option<Duration>,
+}
+
+impl Timeouts {
+    /// Opens a stream to `addr`, trying every address it resolves to.
+    pub fn connect<A: ToSocketAddrs>(&self, addr: A) -> Result<TcpStream, DICTError> {
+        let mut last_err = DICTError::NoAnswer;
+
+        for addr in addr.to_socket_addrs()? {
+            let stream = match self.connect {
+                Some(timeout) => TcpStream::connect_timeout(&addr, timeout),
+                None => TcpStream::connect(addr),
+            };
+
+            match stream {
+                Ok(stream) => {
+                    self.apply(&stream)?;
+                    return Ok(stream);
+                }
+                Err(e) => last_err = io_error(e),
+            }
+        }
+
+        Err(last_err)
+    }
+
+    fn apply(&self, stream: &TcpStream) -> std::io::Result<()> {
+        stream.set_read_timeout(self.read)?;
+        stream.set_write_timeout(self.write)
+    }
+}
+
+fn io_error(e: std::io::Error) -> DICTError {
+    match e.kind() {
+        ErrorKind::TimedOut | ErrorKind::WouldBlock => DICTError::Timeout,
+        _ => DICTError::ReadWriteError(e),
+    }
+}
+
+/// Aborts what a connection is doing, from any thread. The connection can't
+/// be used anymore afterwards.
+#[derive(Clone)]
+pub struct CancelHandle {
+    cancelled: Arc<AtomicBool>,
+    stream: Option<Arc<TcpStream>>,
+}
+
+impl CancelHandle {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+
+        if let Some(ref stream) = self.stream {
+            stream.shutdown(Shutdown::Both).ok();
+        }
+    }
+}
+
 pub struct DICTConnection {
-    input: BufReader<TcpStream>,
-    output: BufWriter<TcpStream>,
+    input: BufReader<Box<dyn Read + Send>>,
+    output: BufWriter<Box<dyn Write + Send>>,
+    machine: Machine,
+
+    // Only known for TCP connections
+    stream: Option<Arc<TcpStream>>,
+    timeouts: Timeouts,
+    deadline: Option<Instant>,
+    cancelled: Arc<AtomicBool>,
+    broken: bool,
 }
 
 impl DICTConnection {
     pub fn new(inner: TcpStream) -> std::io::Result<Self> {
-        let input = BufReader::new(inner.try_clone()?);
-        Ok(DICTConnection {
-            input,
-            output: BufWriter::new(inner),
-        })
+        let stream = inner.try_clone()?;
+        let mut conn = Self::from_io(inner.try_clone()?, inner);
+        conn.stream = Some(Arc::new(stream));
+        Ok(conn)
     }
 
-    fn read_raw_text(&mut self) -> Vec<String> {
-        let mut line = String::new();
-        let mut text = Vec::with_capacity(10);
+    /// Connects to `addr` and enforces `timeouts` on every command.
+    pub fn connect<A: ToSocketAddrs>(addr: A, timeouts: Timeouts) -> Result<Self, DICTError> {
+        let mut conn = Self::new(timeouts.connect(addr)?)?;
+        conn.timeouts = timeouts;
+        Ok(conn)
+    }
 
-        loop {
-            if self.input.read_line(&mut line).is_ok() {
-                let line_t = line.trim_end_matches("\r\n");
-                if line_t.eq(".") {
-                    break;
-                } else {
-                    text.push(line_t.to_owned());
-                    line.clear();
+    /// Talks to a server through any pair of reader and writer.
+    pub fn from_io<R, W>(input: R, output: W) -> Self
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        DICTConnection {
+            input: BufReader::new(Box::new(input)),
+            output: BufWriter::new(Box::new(output)),
+            machine: Machine::new(),
+            stream: None,
+            timeouts: Timeouts::default(),
+            deadline: None,
+            cancelled: Arc::new(AtomicBool::new(false)),
+            broken: false,
+        }
+    }
+
+    /// Like `new`, but every byte exchanged is logged to `recorder`.
+    pub fn recorded(inner: TcpStream, recorder: &Recorder) -> std::io::Result<Self> {
+        let stream = inner.try_clone()?;
+        let mut conn = Self::from_io(recorder.reader(inner.try_clone()?), recorder.writer(inner));
+        conn.stream = Some(Arc::new(stream));
+        Ok(conn)
+    }
+
+    /// Plays back what a server sent in `transcript`, ignoring what is sent.
+    pub fn replay(transcript: &Transcript) -> Self {
+        Self::from_io(transcript.replay(), std::io::sink())
+    }
+
+    pub fn set_timeouts(&mut self, timeouts: Timeouts) -> std::io::Result<()> {
+        if let Some(ref stream) = self.stream {
+            timeouts.apply(stream)?;
+        }
+        self.timeouts = timeouts;
+        Ok(())
+    }
+
+    pub fn cancel_handle(&self) -> CancelHandle {
+        CancelHandle {
+            cancelled: Arc::clone(&self.cancelled),
+            stream: self.stream.clone(),
+        }
+    }
+
+    /// Whether the connection may still be used: it is not after a
+    /// disconnection, a timeout or a cancellation, because the answers
+    /// still to come can't be told apart from the next ones.
+    pub fn is_usable(&self) -> bool {
+        !self.broken && !self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Whether the server is still there, checked without blocking: it is not
+    /// when it closed the connection or sent something unrequested, like a
+    /// 421 before closing.
+    pub fn is_alive(&self) -> bool {
+        if !self.is_usable() || self.machine.is_waiting() || !self.input.buffer().is_empty() {
+            return false;
+        }
+
+        match self.stream {
+            Some(ref stream) => {
+                if stream.set_nonblocking(true).is_err() {
+                    return false;
                 }
+                let peeked = stream.peek(&mut [0u8; 1]);
+                stream.set_nonblocking(false).ok();
+
+                matches!(peeked, Err(ref e) if e.kind() == ErrorKind::WouldBlock)
+            }
+            None => true,
+        }
+    }
+
+    fn fail(&mut self, err: DICTError) -> DICTError {
+        self.broken = true;
+
+        if self.cancelled.load(Ordering::SeqCst) {
+            DICTError::Cancelled
+        } else {
+            err
+        }
+    }
+
+    fn send(&mut self, cmd: Command) -> Result<(), DICTError> {
+        self.deadline = self.timeouts.command.map(|d| Instant::now() + d);
+
+        let bytes = self.machine.send(cmd);
+        match self
+            .output
+            .write_all(&bytes)
+            .and_then(|_| self.output.flush())
+        {
+            Ok(()) => Ok(()),
+            Err(e) => Err(self.fail(io_error(e))),
+        }
+    }
+
+    /// Shortens the read timeout so that reading stops at the deadline.
+    fn arm_deadline(&mut self) -> Result<(), DICTError> {
+        if let (Some(deadline), Some(stream)) = (self.deadline, self.stream.as_ref()) {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining == Duration::from_secs(0) {
+                return Err(DICTError::Timeout);
+            }
+
+            let timeout = match self.timeouts.read {
+                Some(read) if read < remaining => read,
+                _ => remaining,
+            };
+            stream.set_read_timeout(Some(timeout))?;
+        }
+
+        Ok(())
+    }
+
+    /// Gives back the configured read timeout once nothing is awaited.
+    fn disarm_deadline(&mut self) {
+        self.deadline = None;
+
+        if let Some(stream) = self.stream.as_ref() {
+            stream.set_read_timeout(self.timeouts.read).ok();
+        }
+    }
+
+    /// The next event already parsed. The deadline ends with the response.
+    fn poll(&mut self) -> Option<Event> {
+        let event = self.machine.poll();
+        if let Some(Event::Response(_)) = event {
+            self.disarm_deadline();
+        }
+
+        event
+    }
+
+    fn next_event(&mut self) -> Result<Event, DICTError> {
+        loop {
+            if let Some(event) = self.poll() {
+                return Ok(event);
             }
+
+            if let Err(e) = self.arm_deadline() {
+                return Err(self.fail(e));
+            }
+
+            let read = match self.input.fill_buf() {
+                Ok([]) => {
+                    let err = DICTError::ReplyError(ParseReplyError::FailedToRead);
+                    return Err(self.fail(err));
+                }
+                Ok(buf) => {
+                    self.machine.feed(buf);
+                    buf.len()
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => 0,
+                Err(e) => return Err(self.fail(io_error(e))),
+            };
+            self.input.consume(read);
         }
+    }
+
+    /// Waits for the whole response to the oldest command sent.
+    fn response(&mut self) -> Result<Response, DICTError> {
+        let mut error = None;
 
-        text
+        loop {
+            match self.next_event()? {
+                Event::Packet(Err(e)) if error.is_none() => error = Some(e),
+                Event::Response(r) => {
+                    return match error {
+                        Some(e) => Err(e),
+                        None => Ok(r),
+                    }
+                }
+                _ => {}
+            }
+        }
     }
 
-    pub fn start(&mut self) -> DICTResult<String> {
-        match self.next().ok_or(DICTError::NoAnswer)?? {
-            DICTPacket(DICTPacketKind::InitialConnection(msg_id), r) => Ok((msg_id, r)),
-            e => Err(DICTError::UnexpectedPacket(e)),
+    pub fn start(&mut self) -> DICTResult<Banner> {
+        self.deadline = self.timeouts.command.map(|d| Instant::now() + d);
+        let response = self.response()?;
+
+        match response.packets.into_iter().next() {
+            Some(DICTPacket(DICTPacketKind::InitialConnection(banner), r)) => Ok((banner, r)),
+            Some(e) => Err(DICTError::UnexpectedPacket(e)),
+            None => Err(DICTError::NoAnswer),
         }
     }
 
     pub fn client(&mut self, client: String) -> Result<Reply, DICTError> {
-        writeln!(self.output, "CLIENT \"{}\"", client)?;
-        self.output.flush()?;
+        self.send(Command::Client(client))?;
+
+        match self.response()?.packets.into_iter().next() {
+            Some(DICTPacket(DICTPacketKind::OkReply, r)) => Ok(r),
+            Some(e) => Err(DICTError::UnexpectedPacket(e)),
+            None => Err(DICTError::NoAnswer),
+        }
+    }
 
-        match self.next().ok_or(DICTError::NoAnswer)?? {
-            DICTPacket(DICTPacketKind::OkReply, r) => Ok(r),
-            e => Err(DICTError::UnexpectedPacket(e)),
+    /// Authenticates with the shared secret of `user`, `msg_id` being the one
+    /// given by `start`.
+    pub fn auth(&mut self, user: &str, secret: &str, msg_id: &str) -> Result<Reply, DICTError> {
+        let digest = md5::compute(format!("{}{}", msg_id, secret));
+        self.send(Command::Auth(user.to_owned(), format!("{:x}", digest)))?;
+
+        let response = self.response()?;
+        match response.packets.into_iter().next() {
+            Some(DICTPacket(DICTPacketKind::ReplyOnly, r)) => Ok(r),
+            Some(e) => Err(DICTError::UnexpectedPacket(e)),
+            None => Err(DICTError::NoAnswer),
         }
     }
 
@@ -111,39 +410,55 @@ impl DICTConnection {
         database: Database,
         word: String,
     ) -> Result<(Vec<Definition>, Reply), DICTError> {
-        writeln!(self.output, "DEFINE \"{}\" \"{}\"", database.name, word)?;
-        self.output.flush()?;
+        self.send(Command::Define(database, word))?;
 
-        let reply = self.next().ok_or(DICTError::NoAnswer)??;
+        let mut packets = self.response()?.packets.into_iter();
 
         // start of answer
-        match reply {
-            DICTPacket(DICTPacketKind::DefinitionsFollow, _) => {}
+        let reply = match packets.next().ok_or(DICTError::NoAnswer)? {
+            DICTPacket(DICTPacketKind::DefinitionsFollow, r) => r,
             p => {
                 return Err(DICTError::UnexpectedPacket(p));
             }
-        }
+        };
 
         let mut defs: Vec<Definition> = Vec::new();
 
-        for p in self {
+        for p in packets {
             match p {
-                Ok(DICTPacket(DICTPacketKind::Definition(def), _)) => {
+                DICTPacket(DICTPacketKind::Definition(def), _) => {
                     defs.push(def);
                 }
-                Ok(DICTPacket(DICTPacketKind::OkReply, _)) => {
+                DICTPacket(DICTPacketKind::OkReply, _) => {
                     break;
                 }
-                Ok(unexp) => {
+                unexp => {
                     return Err(DICTError::UnexpectedPacket(unexp));
                 }
-                Err(e) => {
-                    return Err(e);
-                }
             }
         }
 
-        Ok((defs, reply.1))
+        Ok((defs, reply))
+    }
+
+    /// Expects a text block, decoded by `extract`, followed by an ok reply.
+    fn listing<T, F>(&mut self, cmd: Command, extract: F) -> Result<(T, Reply), DICTError>
+    where
+        F: FnOnce(DICTPacketKind) -> Result<T, DICTPacketKind>,
+    {
+        self.send(cmd)?;
+
+        let mut packets = self.response()?.packets.into_iter();
+
+        let DICTPacket(kind, r) = packets.next().ok_or(DICTError::NoAnswer)?;
+        let ret = extract(kind)
+            .map_err(|kind| DICTError::UnexpectedPacket(DICTPacket(kind, r.clone())))?;
+
+        match packets.next() {
+            Some(DICTPacket(DICTPacketKind::OkReply, _)) => Ok((ret, r)),
+            Some(ok) => Err(DICTError::UnexpectedPacket(ok)),
+            None => Err(DICTError::NoAnswer),
+        }
     }
 
     pub fn match_db(
@@ -152,258 +467,102 @@ impl DICTConnection {
         strat: Strategy,
         word: String,
     ) -> Result<(Vec<Match>, Reply), DICTError> {
-        writeln!(
-            self.output,
-            "MATCH \"{}\" \"{}\" \"{}\"",
-            db.name, strat.name, word
-        )?;
-        self.output.flush()?;
-
-        match self.next().ok_or(DICTError::NoAnswer)?? {
-            DICTPacket(DICTPacketKind::Matches(matches), r) => {
-                let ok = self.next().ok_or(DICTError::NoAnswer)??;
-
-                if let DICTPacket(DICTPacketKind::OkReply, _) = ok {
-                    Ok((matches, r))
-                } else {
-                    Err(DICTError::UnexpectedPacket(ok))
-                }
-            }
-            e => Err(DICTError::UnexpectedPacket(e)),
-        }
+        self.listing(Command::Match(db, strat, word), |kind| match kind {
+            DICTPacketKind::Matches(matches) => Ok(matches),
+            other => Err(other),
+        })
     }
 
     pub fn show_db(&mut self) -> Result<(Vec<Database>, Reply), DICTError> {
-        writeln!(self.output, "SHOW DATABASES")?;
-        self.output.flush()?;
-
-        match self.next().ok_or(DICTError::NoAnswer)?? {
-            DICTPacket(DICTPacketKind::Databases(dbs), r) => {
-                let ok = self.next().ok_or(DICTError::NoAnswer)??;
-
-                if let DICTPacket(DICTPacketKind::OkReply, _) = ok {
-                    Ok((dbs, r))
-                } else {
-                    Err(DICTError::UnexpectedPacket(ok))
-                }
-            }
-            e => Err(DICTError::UnexpectedPacket(e)),
-        }
+        self.listing(Command::ShowDatabases, |kind| match kind {
+            DICTPacketKind::Databases(dbs) => Ok(dbs),
+            other => Err(other),
+        })
     }
 
     pub fn show_strat(&mut self) -> Result<(Vec<Strategy>, Reply), DICTError> {
-        writeln!(self.output, "SHOW STRATEGIES")?;
-        self.output.flush()?;
+        self.listing(Command::ShowStrategies, |kind| match kind {
+            DICTPacketKind::Strategies(strats) => Ok(strats),
+            other => Err(other),
+        })
+    }
 
-        match self.next().ok_or(DICTError::NoAnswer)?? {
-            DICTPacket(DICTPacketKind::Strategies(strats), r) => {
-                let ok = self.next().ok_or(DICTError::NoAnswer)??;
+    /// The text of `SHOW INFO` about `db`.
+    pub fn show_info(&mut self, db: Database) -> Result<(Vec<String>, Reply), DICTError> {
+        self.listing(Command::ShowInfo(db), text_block)
+    }
 
-                if let DICTPacket(DICTPacketKind::OkReply, _) = ok {
-                    Ok((strats, r))
-                } else {
-                    Err(DICTError::UnexpectedPacket(ok))
-                }
-            }
-            e => Err(DICTError::UnexpectedPacket(e)),
-        }
+    /// The text of `SHOW SERVER`.
+    pub fn show_server(&mut self) -> Result<(Vec<String>, Reply), DICTError> {
+        self.listing(Command::ShowServer, text_block)
     }
-}
 
-macro_rules! get_argument {
-    ($arguments:ident, $index:expr, $err:expr) => {
-        if let Some(arg) = $arguments.get($index) {
-            arg
-        } else {
-            return Some(Err($err));
-        }
-    };
-}
+    /// The statistics of the server are in the text of the reply.
+    pub fn status(&mut self) -> Result<Reply, DICTError> {
+        self.send(Command::Status)?;
 
-fn parse_cmd_argument(reply_text: &String) -> Vec<String> {
-    let mut ret: Vec<String> = Vec::new();
-    let mut tmp: String = String::new();
+        match self.response()?.packets.into_iter().next() {
+            Some(DICTPacket(DICTPacketKind::ReplyOnly, r)) => Ok(r),
+            Some(e) => Err(DICTError::UnexpectedPacket(e)),
+            None => Err(DICTError::NoAnswer),
+        }
+    }
 
-    let mut in_string: bool = false;
+    /// Sends `line` verbatim, and returns everything the server answered up
+    /// to the final reply. Negative replies are answers too, not errors.
+    pub fn raw_command(&mut self, line: &str) -> Result<Vec<RawAnswer>, DICTError> {
+        // Only one line may be sent
+        let line = line.lines().next().unwrap_or("");
+        self.send(Command::Raw(line.to_owned()))?;
 
-    for part in reply_text.split_ascii_whitespace() {
-        if !in_string {
-            // Starting a string
-            if let Some(suffix) = part.strip_prefix('"') {
-                if let Some(oneword) = suffix.strip_suffix('"') {
-                    // That ends here too
-                    ret.push(String::from(oneword));
-                } else {
-                    in_string = true;
+        let mut answers = Vec::new();
+        let mut text = Vec::new();
 
-                    tmp.push_str(suffix);
-                }
-            } else {
-                ret.push(String::from(part));
-            }
-        } else {
-            tmp.push_str(" ");
-            if let Some(preffix) = part.strip_suffix('"') {
-                tmp.push_str(preffix);
-                ret.push(tmp);
-
-                in_string = false;
-                tmp = String::new();
-            } else {
-                tmp.push_str(part);
+        loop {
+            match self.next_event()? {
+                Event::Status(r) => answers.push(RawAnswer::Reply(r)),
+                Event::TextLine(l) => text.push(l),
+                Event::EndOfText => answers.push(RawAnswer::Text(std::mem::take(&mut text))),
+                Event::Packet(_) => {}
+                Event::Response(_) => return Ok(answers),
             }
         }
     }
+}
 
-    ret
+fn text_block(kind: DICTPacketKind) -> Result<Vec<String>, DICTPacketKind> {
+    match kind {
+        DICTPacketKind::Text(text) => Ok(text),
+        // An empty text block
+        DICTPacketKind::ReplyOnly => Ok(Vec::new()),
+        other => Err(other),
+    }
 }
 
 impl Iterator for DICTConnection {
     type Item = Result<DICTPacket, DICTError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let reply = match Reply::from_reader(&mut self.input) {
-            Ok(rep) => rep,
-            Err(e) => {
-                return Some(Err(DICTError::ReplyError(e)));
-            }
-        };
-
-        match reply.status {
-            // Generic
-            Status(ReplyKind::PositiveCompletion, Category::System, 0) => {
-                Some(Ok(DICTPacket(DICTPacketKind::OkReply, reply)))
-            }
-
-            // Connection open
-            Status(ReplyKind::PositiveCompletion, Category::Connection, 0) => {
-                let arguments = reply.text.split_whitespace().collect::<Vec<&str>>();
-
-                let msg_id = get_argument!(
-                    arguments,
-                    arguments.len() - 1,
-                    DICTError::MalformedAnswer("Missing starting text")
-                );
-                Some(Ok(DICTPacket(
-                    DICTPacketKind::InitialConnection((*msg_id).to_owned()),
-                    reply,
-                )))
-            }
-
-            // DEFINE Command
-            Status(ReplyKind::PositivePreliminary, Category::System, 0) => {
-                Some(Ok(DICTPacket(DICTPacketKind::DefinitionsFollow, reply)))
-            }
-            Status(ReplyKind::PositivePreliminary, Category::System, 1) => {
-                // Definition
-
-                let arguments = parse_cmd_argument(&reply.text);
-                let dbname = get_argument!(
-                    arguments,
-                    1,
-                    DICTError::MalformedAnswer("Missing database name")
-                );
-                let dbdesc = get_argument!(
-                    arguments,
-                    2,
-                    DICTError::MalformedAnswer("Missing database description")
-                );
-
-                let text = self.read_raw_text();
-
-                let def = Definition {
-                    source: Database {
-                        name: String::from(dbname),
-                        desc: String::from(dbdesc),
-                    },
-                    text,
-                };
-
-                Some(Ok(DICTPacket(DICTPacketKind::Definition(def), reply)))
-            }
-
-            // MATCH command
-            Status(ReplyKind::PositivePreliminary, Category::System, 2) => {
-                let mut matches: Vec<Match> = Vec::new();
-                for match_def in self.read_raw_text() {
-                    let arguments = parse_cmd_argument(&match_def);
-                    let dbname = get_argument!(
-                        arguments,
-                        0,
-                        DICTError::MalformedAnswer("Missing database name")
-                    );
-                    let word = get_argument!(
-                        arguments,
-                        1,
-                        DICTError::MalformedAnswer("Missing database description")
-                    );
-
-                    matches.push(Match {
-                        source: Database::from(dbname.to_owned()),
-                        word: word.to_owned(),
-                    });
-                }
-
-                Some(Ok(DICTPacket(DICTPacketKind::Matches(matches), reply)))
-            }
-
-            // SHOW DB command
-            Status(ReplyKind::PositivePreliminary, Category::Information, 0) => {
-                let mut dbs: Vec<Database> = Vec::new();
-                for db_def in self.read_raw_text() {
-                    let arguments = parse_cmd_argument(&db_def);
-                    let name = get_argument!(
-                        arguments,
-                        0,
-                        DICTError::MalformedAnswer("Missing database name")
-                    );
-                    let desc = get_argument!(
-                        arguments,
-                        1,
-                        DICTError::MalformedAnswer("Missing database description")
-                    );
-
-                    dbs.push(Database {
-                        name: name.to_owned(),
-                        desc: desc.to_owned(),
-                    });
-                }
-
-                Some(Ok(DICTPacket(DICTPacketKind::Databases(dbs), reply)))
-            }
-
-            // SHOW STRAT command
-            Status(ReplyKind::PositivePreliminary, Category::Information, 1) => {
-                let mut strats: Vec<Strategy> = Vec::new();
-                for strat_def in self.read_raw_text() {
-                    let arguments = parse_cmd_argument(&strat_def);
-                    let name = get_argument!(
-                        arguments,
-                        0,
-                        DICTError::MalformedAnswer("Missing database name")
-                    );
-                    let desc = get_argument!(
-                        arguments,
-                        1,
-                        DICTError::MalformedAnswer("Missing database description")
-                    );
-
-                    strats.push(Strategy {
-                        name: name.to_owned(),
-                        desc: desc.to_owned(),
-                    });
+        loop {
+            match self.next_event() {
+                Ok(Event::Packet(p)) => {
+                    // The response is complete: forget it, so that it is
+                    // not mistaken for the one of the next command.
+                    if let Some(Event::Response(_)) = self.machine.peek() {
+                        self.poll();
+                    }
+
+                    return Some(p);
                 }
-
-                Some(Ok(DICTPacket(DICTPacketKind::Strategies(strats), reply)))
+                Ok(_) => {}
+                Err(e) => return Some(Err(e)),
             }
-            ref r if r.is_positive() => Some(Ok(DICTPacket(DICTPacketKind::ReplyOnly, reply))),
-            _ => Some(Err(DICTError::SystemError(reply))),
         }
     }
 }
 
 impl Drop for DICTConnection {
     fn drop(&mut self) {
-        writeln!(self.output, "QUIT").ok();
+        self.send(Command::Quit).ok();
     }
 }
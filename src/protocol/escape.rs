@@ -0,0 +1,115 @@
+//! Escaping of text and bytes so that they fit on one line and in a field
+//! separated by tabs. Backslashes, tabs and line breaks are escaped with a
+//! backslash, other control characters and bytes that are not UTF-8 as
+//! `\x` and two hexadecimal digits.
+
+fn push_char(ret: &mut String, c: char) {
+    match c {
+        '\\' => ret.push_str("\\\\"),
+        '\r' => ret.push_str("\\r"),
+        '\n' => ret.push_str("\\n"),
+        '\t' => ret.push_str("\\t"),
+        c if c.is_control() => {
+            for b in c.to_string().bytes() {
+                ret.push_str(&format!("\\x{:02x}", b));
+            }
+        }
+        c => ret.push(c),
+    }
+}
+
+pub fn escape_bytes(bytes: &[u8]) -> String {
+    let mut ret = String::with_capacity(bytes.len());
+    let mut rest = bytes;
+
+    while !rest.is_empty() {
+        let (text, invalid) = match std::str::from_utf8(rest) {
+            Ok(text) => (text, &[][..]),
+            Err(e) => {
+                let (valid, invalid) = rest.split_at(e.valid_up_to());
+                (std::str::from_utf8(valid).unwrap(), invalid)
+            }
+        };
+
+        for c in text.chars() {
+            push_char(&mut ret, c);
+        }
+
+        rest = match invalid.split_first() {
+            Some((b, after)) => {
+                ret.push_str(&format!("\\x{:02x}", b));
+                after
+            }
+            None => invalid,
+        };
+    }
+
+    ret
+}
+
+/// `None` when `text` has an unknown or incomplete escape.
+pub fn unescape_bytes(text: &str) -> Option<Vec<u8>> {
+    let mut ret = Vec::with_capacity(text.len());
+    let mut bytes = text.bytes();
+
+    while let Some(b) = bytes.next() {
+        if b != b'\\' {
+            ret.push(b);
+            continue;
+        }
+
+        match bytes.next()? {
+            b'\\' => ret.push(b'\\'),
+            b'r' => ret.push(b'\r'),
+            b'n' => ret.push(b'\n'),
+            b't' => ret.push(b'\t'),
+            b'x' => {
+                let hex = [bytes.next()?, bytes.next()?];
+                let hex = std::str::from_utf8(&hex).ok()?;
+                ret.push(u8::from_str_radix(hex, 16).ok()?);
+            }
+            _ => return None,
+        }
+    }
+
+    Some(ret)
+}
+
+pub fn escape(field: &str) -> String {
+    escape_bytes(field.as_bytes())
+}
+
+/// Keeps `field` as it is when it can't be unescaped, as it was probably
+/// written by hand.
+pub fn unescape(field: &str) -> String {
+    match unescape_bytes(field) {
+        Some(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        None => field.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bytes() {
+        let raw = b"220 \"dict\" \\ \t\x01\xff\r\n";
+        assert_eq!(escape_bytes(raw), "220 \"dict\" \\\\ \\t\\x01\\xff\\r\\n");
+        assert_eq!(unescape_bytes(&escape_bytes(raw)).unwrap(), raw.to_vec());
+    }
+
+    #[test]
+    fn text() {
+        let field = "a\tb\\nc\nd é";
+        assert_eq!(escape(field), "a\\tb\\\\nc\\nd é");
+        assert_eq!(unescape(&escape(field)), field);
+    }
+
+    #[test]
+    fn invalid_escape() {
+        assert!(unescape_bytes("\\q").is_none());
+        assert!(unescape_bytes("\\x4").is_none());
+        assert_eq!(unescape("C:\\q"), "C:\\q");
+    }
+}
@@ -0,0 +1,550 @@
+//! The DICT protocol, without any IO.
+//!
+//! A `Machine` is told which commands are sent with `send`, is fed with the
+//! bytes received with `feed`, and then emits `Event`s through `poll`. It
+//! never reads nor writes anything by itself, so that any kind of client can
+//! share it, and so that it can be tested with arbitrary input.
+use super::{Database, Definition, Match, Strategy};
+use crate::connection::{Banner, DICTError, DICTPacket, DICTPacketKind};
+use crate::reply::Reply;
+use crate::status::{Category, ReplyKind, Status};
+use std::collections::VecDeque;
+use std::fmt::Display;
+
+#[derive(Debug, Clone)]
+pub enum Command {
+    Client(String),
+    /// User name and authentication string
+    Auth(String, String),
+    Define(Database, String),
+    Match(Database, Strategy, String),
+    ShowDatabases,
+    ShowStrategies,
+    ShowInfo(Database),
+    ShowServer,
+    Status,
+    Quit,
+    /// Any line, sent as is
+    Raw(String),
+}
+
+impl Display for Command {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Command::Client(client) => write!(f, "CLIENT \"{}\"", client),
+            Command::Auth(user, auth) => write!(f, "AUTH {} {}", user, auth),
+            Command::Define(db, word) => write!(f, "DEFINE \"{}\" \"{}\"", db.name, word),
+            Command::Match(db, strat, word) => {
+                write!(f, "MATCH \"{}\" \"{}\" \"{}\"", db.name, strat.name, word)
+            }
+            Command::ShowDatabases => write!(f, "SHOW DATABASES"),
+            Command::ShowStrategies => write!(f, "SHOW STRATEGIES"),
+            Command::ShowInfo(db) => write!(f, "SHOW INFO \"{}\"", db.name),
+            Command::ShowServer => write!(f, "SHOW SERVER"),
+            Command::Status => write!(f, "STATUS"),
+            Command::Quit => write!(f, "QUIT"),
+            Command::Raw(line) => write!(f, "{}", line),
+        }
+    }
+}
+
+impl Command {
+    /// The bytes to send to the server, line ending included.
+    pub fn encode(&self) -> Vec<u8> {
+        format!("{}\r\n", self).into_bytes()
+    }
+}
+
+/// Everything the server answered to a command, up to its final status.
+#[derive(Debug)]
+pub struct Response {
+    /// `None` for the initial banner or for unsolicited replies.
+    pub command: Option<Command>,
+    /// Every packet received, the final one included.
+    pub packets: Vec<DICTPacket>,
+    /// `None` when the status line could not be read.
+    pub reply: Option<Reply>,
+}
+
+impl Response {
+    pub fn is_positive(&self) -> bool {
+        match self.reply {
+            Some(ref reply) => reply.status.is_positive(),
+            None => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Event {
+    Status(Reply),
+    TextLine(String),
+    EndOfText,
+    Packet(Result<DICTPacket, DICTError>),
+    Response(Response),
+}
+
+enum State {
+    Status,
+    Text(Reply, Vec<String>),
+}
+
+pub struct Machine {
+    buffer: Vec<u8>,
+    state: State,
+    pending: VecDeque<Option<Command>>,
+    packets: Vec<DICTPacket>,
+    events: VecDeque<Event>,
+}
+
+impl Default for Machine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Machine {
+    /// A machine waiting for the banner of the server.
+    pub fn new() -> Self {
+        let mut pending = VecDeque::new();
+        pending.push_back(None);
+
+        Machine {
+            buffer: Vec::new(),
+            state: State::Status,
+            pending,
+            packets: Vec::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    /// Registers `cmd` as sent, and returns what has to be sent.
+    pub fn send(&mut self, cmd: Command) -> Vec<u8> {
+        let bytes = cmd.encode();
+        self.pending.push_back(Some(cmd));
+        bytes
+    }
+
+    /// Whether some responses are still expected.
+    pub fn is_waiting(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+
+        while let Some(pos) = self.buffer.iter().position(|b| *b == b'\n') {
+            let raw: Vec<u8> = self.buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&raw);
+            self.line(line.trim_end_matches(&['\r', '\n'][..]));
+        }
+    }
+
+    pub fn poll(&mut self) -> Option<Event> {
+        self.events.pop_front()
+    }
+
+    pub fn peek(&self) -> Option<&Event> {
+        self.events.front()
+    }
+
+    fn line(&mut self, line: &str) {
+        match std::mem::replace(&mut self.state, State::Status) {
+            State::Status => match Reply::from_line(line.to_owned()) {
+                Ok(reply) => {
+                    self.events.push_back(Event::Status(reply.clone()));
+
+                    if has_text(&reply.status) {
+                        self.state = State::Text(reply, Vec::new());
+                    } else {
+                        self.packet(reply, Vec::new());
+                    }
+                }
+                // Whatever it was, it ends the response: the next status
+                // line is for the next command
+                Err(e) => {
+                    self.events
+                        .push_back(Event::Packet(Err(DICTError::ReplyError(e))));
+                    self.respond(None);
+                }
+            },
+            State::Text(reply, mut text) => {
+                if line == "." {
+                    self.events.push_back(Event::EndOfText);
+                    self.packet(reply, text);
+                } else {
+                    // Lines starting with a dot are escaped by doubling it
+                    let line = line.strip_prefix('.').unwrap_or(line).to_owned();
+
+                    self.events.push_back(Event::TextLine(line.clone()));
+                    text.push(line);
+                    self.state = State::Text(reply, text);
+                }
+            }
+        }
+    }
+
+    fn packet(&mut self, reply: Reply, text: Vec<String>) {
+        let is_final = is_final(&reply.status);
+        let packet = parse_packet(reply.clone(), text);
+
+        if let Ok(p) = &packet {
+            self.packets.push(p.clone());
+        }
+        self.events.push_back(Event::Packet(packet));
+
+        if is_final {
+            self.respond(Some(reply));
+        }
+    }
+
+    fn respond(&mut self, reply: Option<Reply>) {
+        self.events.push_back(Event::Response(Response {
+            command: self.pending.pop_front().flatten(),
+            packets: std::mem::take(&mut self.packets),
+            reply,
+        }));
+    }
+}
+
+fn has_text(status: &Status) -> bool {
+    match status {
+        Status(ReplyKind::PositivePreliminary, Category::Information, n) => *n <= 4,
+        Status(ReplyKind::PositivePreliminary, Category::System, n) => *n == 1 || *n == 2,
+        _ => false,
+    }
+}
+
+fn is_final(status: &Status) -> bool {
+    status.0 != ReplyKind::PositivePreliminary
+}
+
+fn argument(arguments: &[String], index: usize, err: &'static str) -> Result<String, DICTError> {
+    arguments
+        .get(index)
+        .cloned()
+        .ok_or(DICTError::MalformedAnswer(err))
+}
+
+fn parse_cmd_argument(reply_text: &str) -> Vec<String> {
+    let mut ret: Vec<String> = Vec::new();
+    let mut tmp: String = String::new();
+
+    let mut in_string: bool = false;
+
+    for part in reply_text.split_ascii_whitespace() {
+        if !in_string {
+            // Starting a string
+            if let Some(suffix) = part.strip_prefix('"') {
+                if let Some(oneword) = suffix.strip_suffix('"') {
+                    // That ends here too
+                    ret.push(String::from(oneword));
+                } else {
+                    in_string = true;
+
+                    tmp.push_str(suffix);
+                }
+            } else {
+                ret.push(String::from(part));
+            }
+        } else {
+            tmp.push(' ');
+            if let Some(preffix) = part.strip_suffix('"') {
+                tmp.push_str(preffix);
+                ret.push(tmp);
+
+                in_string = false;
+                tmp = String::new();
+            } else {
+                tmp.push_str(part);
+            }
+        }
+    }
+
+    ret
+}
+
+fn parse_packet(reply: Reply, text: Vec<String>) -> Result<DICTPacket, DICTError> {
+    let kind = match reply.status {
+        // Generic
+        Status(ReplyKind::PositiveCompletion, Category::System, 0) => DICTPacketKind::OkReply,
+
+        // Connection open
+        Status(ReplyKind::PositiveCompletion, Category::Connection, 0) => {
+            // Some text, then the capabilities and the msg-id in angle brackets
+            let mut parts = reply.text.split_whitespace().rev();
+            let msg_id = parts
+                .next()
+                .ok_or(DICTError::MalformedAnswer("Missing starting text"))?;
+            let capabilities = match parts.next() {
+                Some(caps) if caps.starts_with('<') && caps.ends_with('>') => caps
+                    [1..caps.len() - 1]
+                    .split('.')
+                    .filter(|cap| !cap.is_empty())
+                    .map(String::from)
+                    .collect(),
+                _ => Vec::new(),
+            };
+
+            DICTPacketKind::InitialConnection(Banner {
+                msg_id: msg_id.to_owned(),
+                capabilities,
+            })
+        }
+
+        // DEFINE Command
+        Status(ReplyKind::PositivePreliminary, Category::System, 0) => {
+            DICTPacketKind::DefinitionsFollow
+        }
+        Status(ReplyKind::PositivePreliminary, Category::System, 1) => {
+            let arguments = parse_cmd_argument(&reply.text);
+            let name = argument(&arguments, 1, "Missing database name")?;
+            let desc = argument(&arguments, 2, "Missing database description")?;
+
+            DICTPacketKind::Definition(Definition {
+                source: Database { name, desc },
+                text,
+            })
+        }
+
+        // MATCH command
+        Status(ReplyKind::PositivePreliminary, Category::System, 2) => {
+            let mut matches: Vec<Match> = Vec::new();
+            for match_def in text {
+                let arguments = parse_cmd_argument(&match_def);
+                let name = argument(&arguments, 0, "Missing database name")?;
+                let word = argument(&arguments, 1, "Missing matched word")?;
+
+                matches.push(Match {
+                    source: Database::from(name),
+                    word,
+                });
+            }
+
+            DICTPacketKind::Matches(matches)
+        }
+
+        // SHOW DB command
+        Status(ReplyKind::PositivePreliminary, Category::Information, 0) => {
+            let mut dbs: Vec<Database> = Vec::new();
+            for db_def in text {
+                let arguments = parse_cmd_argument(&db_def);
+                let name = argument(&arguments, 0, "Missing database name")?;
+                let desc = argument(&arguments, 1, "Missing database description")?;
+
+                dbs.push(Database { name, desc });
+            }
+
+            DICTPacketKind::Databases(dbs)
+        }
+
+        // SHOW STRAT command
+        Status(ReplyKind::PositivePreliminary, Category::Information, 1) => {
+            let mut strats: Vec<Strategy> = Vec::new();
+            for strat_def in text {
+                let arguments = parse_cmd_argument(&strat_def);
+                let name = argument(&arguments, 0, "Missing strategy name")?;
+                let desc = argument(&arguments, 1, "Missing strategy description")?;
+
+                strats.push(Strategy { name, desc });
+            }
+
+            DICTPacketKind::Strategies(strats)
+        }
+        _ if !text.is_empty() => DICTPacketKind::Text(text),
+        ref r if r.is_positive() => DICTPacketKind::ReplyOnly,
+        _ => return Err(DICTError::SystemError(reply)),
+    };
+
+    Ok(DICTPacket(kind, reply))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SESSION: &[u8] = b"220 dict.org dictd <auth.mime> <1.2@dict.org>\r\n\
+        150 1 definitions retrieved\r\n\
+        151 \"ti\" wn \"WordNet (r) 3.0 (2006)\"\r\n\
+        ti\r\n\
+        ..dotted\r\n\
+        .\r\n\
+        250 ok\r\n";
+
+    fn events(machine: &mut Machine) -> Vec<Event> {
+        std::iter::from_fn(|| machine.poll()).collect()
+    }
+
+    fn responses(events: Vec<Event>) -> Vec<Response> {
+        events
+            .into_iter()
+            .filter_map(|e| match e {
+                Event::Response(r) => Some(r),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn command_encoding() {
+        let cmd = Command::Match(Database::all(), Strategy::prefix(), String::from("ti"));
+        assert_eq!(cmd.encode(), b"MATCH \"*\" \"prefix\" \"ti\"\r\n".to_vec());
+    }
+
+    #[test]
+    fn banner_without_capabilities() {
+        let mut machine = Machine::new();
+        machine.feed(b"220 dict.org dictd <1.2@dict.org>\r\n");
+
+        match &responses(events(&mut machine))[0].packets[..] {
+            [DICTPacket(DICTPacketKind::InitialConnection(banner), _)] => {
+                assert_eq!(banner.msg_id, "<1.2@dict.org>");
+                assert!(banner.capabilities.is_empty());
+            }
+            p => panic!("Unexpected packets: {:?}", p),
+        }
+    }
+
+    #[test]
+    fn define_session() {
+        let mut machine = Machine::new();
+        machine.send(Command::Define(Database::all(), String::from("ti")));
+        machine.feed(SESSION);
+
+        let responses = responses(events(&mut machine));
+        assert_eq!(responses.len(), 2);
+
+        match &responses[0].packets[..] {
+            [DICTPacket(DICTPacketKind::InitialConnection(banner), _)] => {
+                assert_eq!(banner.msg_id, "<1.2@dict.org>");
+                assert_eq!(banner.capabilities, vec!["auth", "mime"]);
+            }
+            p => panic!("Unexpected packets: {:?}", p),
+        }
+
+        assert!(matches!(responses[1].command, Some(Command::Define(_, _))));
+        match &responses[1].packets[..] {
+            [DICTPacket(DICTPacketKind::DefinitionsFollow, _), DICTPacket(DICTPacketKind::Definition(def), _), DICTPacket(DICTPacketKind::OkReply, _)] =>
+            {
+                assert_eq!(def.source.name, "wn");
+                assert_eq!(def.text, vec!["ti", ".dotted"]);
+            }
+            p => panic!("Unexpected packets: {:?}", p),
+        }
+        assert!(!machine.is_waiting());
+    }
+
+    #[test]
+    fn byte_by_byte() {
+        let mut machine = Machine::new();
+        machine.send(Command::Define(Database::all(), String::from("ti")));
+
+        let mut all = Vec::new();
+        for b in SESSION {
+            machine.feed(&[*b]);
+            all.extend(events(&mut machine));
+        }
+
+        let kinds: Vec<&str> = all
+            .iter()
+            .map(|e| match e {
+                Event::Status(_) => "status",
+                Event::TextLine(_) => "line",
+                Event::EndOfText => "end",
+                Event::Packet(_) => "packet",
+                Event::Response(_) => "response",
+            })
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                "status", "packet", "response", // Banner
+                "status", "packet", // 150
+                "status", "line", "line", "end", "packet", // 151
+                "status", "packet", "response", // 250
+            ]
+        );
+    }
+
+    #[test]
+    fn negative_reply() {
+        let mut machine = Machine::new();
+        machine.feed(b"220 <1@x>\r\n");
+        events(&mut machine);
+
+        machine.send(Command::Define(Database::all(), String::from("tii")));
+        machine.feed(b"552 no match\r\n");
+
+        let all = events(&mut machine);
+        assert!(matches!(
+            all[1],
+            Event::Packet(Err(DICTError::SystemError(_)))
+        ));
+        assert!(!responses(all)[0].is_positive());
+    }
+
+    #[test]
+    fn unreadable_status_line() {
+        let mut machine = Machine::new();
+        machine.feed(b"220 <1@x>\r\n");
+        events(&mut machine);
+
+        machine.send(Command::Define(Database::all(), String::from("ti")));
+        machine.send(Command::Status);
+        machine.feed(b"garbage\r\n210 status\r\n");
+
+        let responses = responses(events(&mut machine));
+        assert_eq!(responses.len(), 2);
+        assert!(matches!(responses[0].command, Some(Command::Define(_, _))));
+        assert!(responses[0].reply.is_none());
+        assert!(matches!(responses[1].command, Some(Command::Status)));
+        assert!(responses[1].is_positive());
+        assert!(!machine.is_waiting());
+    }
+
+    #[test]
+    fn non_ascii_status_line() {
+        for input in [&b"\xc3\xa9\xc3\xa9\xc3\xa9\r\n"[..], b"2\xff0 hi\r\n"] {
+            let mut machine = Machine::new();
+            machine.feed(input);
+
+            let events = events(&mut machine);
+            assert!(matches!(
+                events[..],
+                [Event::Packet(Err(DICTError::ReplyError(_))), ..]
+            ));
+        }
+    }
+
+    #[test]
+    fn malformed_input() {
+        let mut machine = Machine::new();
+        machine.feed(b"garbage\r\n151 x\r\n.\r\n152 \r\nwn\r\n.\r\n\r\n220\r\n");
+
+        // Only assert aliveness
+        events(&mut machine);
+    }
+
+    #[test]
+    fn arbitrary_input() {
+        // Cheap fuzzing, with a fixed xorshift generator
+        let mut state: u32 = 0x2628;
+        let alphabet = b"0125 .\"\r\nab\xc3\xa9\xff";
+
+        for _ in 0..200 {
+            let mut machine = Machine::new();
+            let input: Vec<u8> = (0..256)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    alphabet[state as usize % alphabet.len()]
+                })
+                .collect();
+
+            machine.feed(&input);
+            events(&mut machine);
+        }
+    }
+}
@@ -1,12 +1,18 @@
+pub mod client;
 pub mod connection;
+pub mod escape;
+pub mod machine;
+pub mod pool;
 pub mod reply;
 pub mod status;
+pub mod testing;
+pub mod transcript;
 pub mod url;
 
 use std::convert::From;
 use std::default::Default;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Database {
     pub name: String,
     pub desc: String,
@@ -43,7 +49,7 @@ impl Default for Database {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Definition {
     pub source: Database,
     pub text: Vec<String>,
@@ -58,7 +64,7 @@ impl Definition {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Strategy {
     pub name: String,
     pub desc: String,
@@ -92,7 +98,7 @@ impl Default for Strategy {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Match {
     pub source: Database,
     pub word: String,
@@ -0,0 +1,242 @@
+//! Several connections to the same server, shared between threads.
+use super::{Database, Definition, Match, Strategy};
+use crate::client::is_disconnection;
+use crate::connection::{DICTConnection, DICTError, Timeouts};
+use crate::reply::Reply;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    /// How many connections may be open at once.
+    pub max_size: usize,
+    /// Idle connections older than that are closed.
+    pub idle_timeout: Duration,
+    /// How long to wait for a connection when all of them are in use.
+    pub checkout_timeout: Option<Duration>,
+    pub timeouts: Timeouts,
+    /// The name sent with `CLIENT` on each connection.
+    pub client: String,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            max_size: 4,
+            idle_timeout: Duration::from_secs(60),
+            checkout_timeout: None,
+            timeouts: Timeouts::default(),
+            client: String::from("redict"),
+        }
+    }
+}
+
+struct Idle {
+    conn: DICTConnection,
+    since: Instant,
+}
+
+struct State {
+    idle: Vec<Idle>,
+    open: usize,
+}
+
+struct Inner {
+    host: String,
+    port: u16,
+    config: PoolConfig,
+    state: Mutex<State>,
+    released: Condvar,
+}
+
+impl Inner {
+    fn open(&self) -> Result<DICTConnection, DICTError> {
+        let mut conn =
+            DICTConnection::connect((self.host.as_str(), self.port), self.config.timeouts)?;
+        conn.start()?;
+        conn.client(self.config.client.clone())?;
+        Ok(conn)
+    }
+
+    fn release(&self, conn: DICTConnection) {
+        let mut state = self.state.lock().unwrap();
+
+        if conn.is_usable() {
+            state.idle.push(Idle {
+                conn,
+                since: Instant::now(),
+            });
+        } else {
+            state.open -= 1;
+        }
+
+        self.released.notify_one();
+    }
+}
+
+/// A pool of connections to one server. Clones share the same connections.
+#[derive(Clone)]
+pub struct DICTPool {
+    inner: Arc<Inner>,
+}
+
+impl DICTPool {
+    /// Does not connect yet: connections are opened when needed.
+    pub fn new(host: &str, port: u16, config: PoolConfig) -> Self {
+        DICTPool {
+            inner: Arc::new(Inner {
+                host: host.to_owned(),
+                port,
+                config,
+                state: Mutex::new(State {
+                    idle: Vec::new(),
+                    open: 0,
+                }),
+                released: Condvar::new(),
+            }),
+        }
+    }
+
+    /// How many connections are open, idle or not.
+    pub fn size(&self) -> usize {
+        self.inner.state.lock().unwrap().open
+    }
+
+    pub fn idle(&self) -> usize {
+        self.inner.state.lock().unwrap().idle.len()
+    }
+
+    /// Borrows a connection, which goes back to the pool when dropped.
+    pub fn get(&self) -> Result<PooledConnection, DICTError> {
+        let deadline = self
+            .inner
+            .config
+            .checkout_timeout
+            .map(|d| Instant::now() + d);
+        let mut state = self.inner.state.lock().unwrap();
+
+        loop {
+            // Evict stale connections, then check the most recent one
+            let idle_timeout = self.inner.config.idle_timeout;
+            let (fresh, mut evicted): (Vec<_>, Vec<_>) = std::mem::take(&mut state.idle)
+                .into_iter()
+                .partition(|i| i.since.elapsed() < idle_timeout);
+            state.idle = fresh;
+            state.open -= evicted.len();
+
+            while let Some(idle) = state.idle.pop() {
+                if idle.conn.is_alive() {
+                    drop(state);
+                    drop(evicted);
+
+                    return Ok(PooledConnection {
+                        conn: Some(idle.conn),
+                        pool: Arc::clone(&self.inner),
+                    });
+                }
+                state.open -= 1;
+                evicted.push(idle);
+            }
+
+            // Closing them sends QUIT, which must not hold the lock
+            if !evicted.is_empty() {
+                drop(state);
+                drop(evicted);
+                state = self.inner.state.lock().unwrap();
+                continue;
+            }
+
+            if state.open < self.inner.config.max_size {
+                state.open += 1;
+                drop(state);
+
+                return match self.inner.open() {
+                    Ok(conn) => Ok(PooledConnection {
+                        conn: Some(conn),
+                        pool: Arc::clone(&self.inner),
+                    }),
+                    Err(e) => {
+                        self.inner.state.lock().unwrap().open -= 1;
+                        self.inner.released.notify_one();
+                        Err(e)
+                    }
+                };
+            }
+
+            state = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining == Duration::from_secs(0) {
+                        return Err(DICTError::Timeout);
+                    }
+                    self.inner
+                        .released
+                        .wait_timeout(state, remaining)
+                        .unwrap()
+                        .0
+                }
+                None => self.inner.released.wait(state).unwrap(),
+            };
+        }
+    }
+
+    /// Runs `f` on a pooled connection, and once more on a new one if the
+    /// server closed the first one meanwhile.
+    fn with<T, F>(&self, mut f: F) -> Result<T, DICTError>
+    where
+        F: FnMut(&mut DICTConnection) -> Result<T, DICTError>,
+    {
+        // The first connection must be back before borrowing another one
+        let first = f(&mut *self.get()?);
+
+        match first {
+            Err(e) if is_disconnection(&e) => f(&mut *self.get()?),
+            ret => ret,
+        }
+    }
+
+    pub fn define(
+        &self,
+        db: Database,
+        word: String,
+    ) -> Result<(Vec<Definition>, Reply), DICTError> {
+        self.with(|conn| conn.define(db.clone(), word.clone()))
+    }
+
+    pub fn match_db(
+        &self,
+        db: Database,
+        strat: Strategy,
+        word: String,
+    ) -> Result<(Vec<Match>, Reply), DICTError> {
+        self.with(|conn| conn.match_db(db.clone(), strat.clone(), word.clone()))
+    }
+}
+
+pub struct PooledConnection {
+    conn: Option<DICTConnection>,
+    pool: Arc<Inner>,
+}
+
+impl Deref for PooledConnection {
+    type Target = DICTConnection;
+
+    fn deref(&self) -> &DICTConnection {
+        self.conn.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledConnection {
+    fn deref_mut(&mut self) -> &mut DICTConnection {
+        self.conn.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            self.pool.release(conn);
+        }
+    }
+}
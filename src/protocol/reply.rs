@@ -1,7 +1,6 @@
 use crate::status::{ParseStatusError, Status};
 use std::io::BufRead;
 use std::str::FromStr;
-use std::string::ToString;
 
 use std::error::Error;
 use std::fmt::Display;
@@ -47,7 +46,7 @@ impl Reply {
         T: BufRead,
     {
         // Assumes that we are actually reading a reply
-        let mut iter = r.lines().filter_map(|l| l.ok());
+        let mut iter = r.lines().map_while(Result::ok);
 
         let firstline = iter.next().ok_or(ParseReplyError::FailedToRead)?;
 
@@ -55,12 +54,12 @@ impl Reply {
     }
 
     pub fn from_line(line: String) -> Result<Self, ParseReplyError> {
-        if line.len() < 3 {
-            return Err(ParseReplyError::FailedToRead);
-        }
-        let (statustxt, text) = line.split_at(3);
+        // A server may send anything, even characters that straddle the
+        // end of the status
+        let statustxt = line.get(..3).ok_or(ParseReplyError::FailedToRead)?;
+        let text = &line[3..];
 
-        let status = Status::from_str(statustxt).map_err(|e| ParseReplyError::Status(e))?;
+        let status = Status::from_str(statustxt).map_err(ParseReplyError::Status)?;
 
         Ok(Reply {
             status,
@@ -69,8 +68,8 @@ impl Reply {
     }
 }
 
-impl ToString for Reply {
-    fn to_string(&self) -> String {
-        String::from(format!("{} {}", self.status, self.text))
+impl Display for Reply {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.status, self.text)
     }
 }
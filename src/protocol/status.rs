@@ -120,10 +120,10 @@ impl FromStr for Status {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let chars: Vec<char> = s.chars().collect();
 
-        let reply = chars.get(0).ok_or(ParseStatusError::InvalidReplyKind)?;
+        let reply = chars.first().ok_or(ParseStatusError::InvalidReplyKind)?;
         let category = chars.get(1).ok_or(ParseStatusError::InvalidCategory)?;
 
-        let errnr: u8 = if let Some(ref c) = chars.get(2) {
+        let errnr: u8 = if let Some(c) = chars.get(2) {
             let tmp = c.to_digit(10).ok_or(ParseStatusError::MissingErrNr)?;
             u8::try_from(tmp).map_err(|_| ParseStatusError::MissingErrNr)?
         } else {
@@ -146,12 +146,12 @@ impl Display for Status {
 
 impl Status {
     pub fn is_positive(&self) -> bool {
-        match self.0 {
+        matches!(
+            self.0,
             ReplyKind::PositiveCompletion
-            | ReplyKind::PositiveIntermediate
-            | ReplyKind::PositivePreliminary => true,
-            _ => false,
-        }
+                | ReplyKind::PositiveIntermediate
+                | ReplyKind::PositivePreliminary
+        )
     }
 
     pub fn is_start(&self) -> bool {
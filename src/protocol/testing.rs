@@ -0,0 +1,391 @@
+//! An in-process, scriptable DICT server to test clients without a real dictd.
+//!
+//! ```no_run
+//! use dictproto::connection::DICTConnection;
+//! use dictproto::testing::{MockReply, MockServer};
+//! use dictproto::Database;
+//!
+//! let server = MockServer::new()
+//!     .on("DEFINE \"*\" \"ti\"", MockReply::error(552, "no match"))
+//!     .start()
+//!     .unwrap();
+//!
+//! let mut conn = DICTConnection::new(server.connect().unwrap()).unwrap();
+//! conn.start().unwrap();
+//! assert!(conn.define(Database::all(), String::from("ti")).is_err());
+//! ```
+use super::{Database, Definition, Match, Strategy};
+use crate::connection::DICTConnection;
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+#[derive(Clone, Debug)]
+enum Action {
+    Line(String),
+    Sleep(Duration),
+    Disconnect,
+}
+
+/// What the server sends back, step by step, when it receives a command.
+#[derive(Clone, Debug, Default)]
+pub struct MockReply(Vec<Action>);
+
+impl MockReply {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sends a status line, like `250 ok`.
+    pub fn status(mut self, code: u16, text: &str) -> Self {
+        self.0.push(Action::Line(format!("{} {}", code, text)));
+        self
+    }
+
+    /// Sends a text block, dot-stuffed and terminated by a single `.`.
+    pub fn text<S: AsRef<str>>(mut self, lines: &[S]) -> Self {
+        for line in lines {
+            let line = line.as_ref();
+            if line.starts_with('.') {
+                self.0.push(Action::Line(format!(".{}", line)));
+            } else {
+                self.0.push(Action::Line(line.to_owned()));
+            }
+        }
+        self.0.push(Action::Line(String::from(".")));
+        self
+    }
+
+    /// Sends a line as is, without any check.
+    pub fn raw(mut self, line: &str) -> Self {
+        self.0.push(Action::Line(line.to_owned()));
+        self
+    }
+
+    /// Waits before going on with the next step.
+    pub fn delay(mut self, duration: Duration) -> Self {
+        self.0.push(Action::Sleep(duration));
+        self
+    }
+
+    /// Closes the connection abruptly.
+    pub fn disconnect(mut self) -> Self {
+        self.0.push(Action::Disconnect);
+        self
+    }
+
+    pub fn ok() -> Self {
+        Self::new().status(250, "ok")
+    }
+
+    pub fn error(code: u16, text: &str) -> Self {
+        Self::new().status(code, text)
+    }
+
+    pub fn banner(msg_id: &str) -> Self {
+        Self::new().status(220, &format!("mock.test dictd <mime> {}", msg_id))
+    }
+
+    pub fn definitions(word: &str, defs: &[Definition]) -> Self {
+        let mut reply = Self::new().status(150, &format!("{} definitions retrieved", defs.len()));
+
+        for def in defs {
+            reply = reply
+                .status(
+                    151,
+                    &format!("\"{}\" {} \"{}\"", word, def.source.name, def.source.desc),
+                )
+                .text(&def.text);
+        }
+
+        reply.status(250, "ok")
+    }
+
+    pub fn matches(matches: &[Match]) -> Self {
+        let lines: Vec<String> = matches
+            .iter()
+            .map(|m| format!("{} \"{}\"", m.source.name, m.word))
+            .collect();
+
+        Self::new()
+            .status(152, &format!("{} matches found", matches.len()))
+            .text(&lines)
+            .status(250, "ok")
+    }
+
+    pub fn databases(dbs: &[Database]) -> Self {
+        let lines: Vec<String> = dbs
+            .iter()
+            .map(|db| format!("{} \"{}\"", db.name, db.desc))
+            .collect();
+
+        Self::new()
+            .status(110, &format!("{} databases present", dbs.len()))
+            .text(&lines)
+            .status(250, "ok")
+    }
+
+    /// A text block of `code`, like the answer to `SHOW INFO`.
+    pub fn text_block<S: AsRef<str>>(code: u16, lines: &[S]) -> Self {
+        Self::new()
+            .status(code, "information follows")
+            .text(lines)
+            .status(250, "ok")
+    }
+
+    pub fn strategies(strats: &[Strategy]) -> Self {
+        let lines: Vec<String> = strats
+            .iter()
+            .map(|s| format!("{} \"{}\"", s.name, s.desc))
+            .collect();
+
+        Self::new()
+            .status(111, &format!("{} strategies present", strats.len()))
+            .text(&lines)
+            .status(250, "ok")
+    }
+}
+
+struct Script {
+    banner: MockReply,
+    replies: Vec<(String, VecDeque<MockReply>)>,
+    received: Vec<String>,
+}
+
+impl Script {
+    /// Replies registered several times for the same command are used in
+    /// turn, the last one being repeated forever.
+    fn reply_for(&mut self, command: &str) -> MockReply {
+        self.received.push(command.to_owned());
+
+        match self.replies.iter_mut().find(|(cmd, _)| cmd == command) {
+            Some((_, queue)) if queue.len() > 1 => queue.pop_front().unwrap(),
+            Some((_, queue)) => queue.front().cloned().unwrap_or_default(),
+            None => MockReply::error(500, "unknown command"),
+        }
+    }
+}
+
+pub struct MockServer {
+    script: Script,
+}
+
+impl Default for MockServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockServer {
+    /// A server that greets clients and accepts `CLIENT`, but knows nothing
+    /// else yet.
+    pub fn new() -> Self {
+        let script = Script {
+            banner: MockReply::banner("<1.1@mock.test>"),
+            replies: Vec::new(),
+            received: Vec::new(),
+        };
+
+        MockServer { script }.on("CLIENT \"redict\"", MockReply::ok())
+    }
+
+    pub fn banner(mut self, reply: MockReply) -> Self {
+        self.script.banner = reply;
+        self
+    }
+
+    /// Answers `reply` to the exact `command` line.
+    pub fn on(mut self, command: &str, reply: MockReply) -> Self {
+        if let Some((_, queue)) = self
+            .script
+            .replies
+            .iter_mut()
+            .find(|(cmd, _)| cmd == command)
+        {
+            queue.push_back(reply);
+        } else {
+            let mut queue = VecDeque::new();
+            queue.push_back(reply);
+            self.script.replies.push((command.to_owned(), queue));
+        }
+        self
+    }
+
+    pub fn define(self, db: &str, word: &str, defs: &[Definition]) -> Self {
+        self.on(
+            &format!("DEFINE \"{}\" \"{}\"", db, word),
+            MockReply::definitions(word, defs),
+        )
+    }
+
+    pub fn match_db(self, db: &str, strat: &str, word: &str, matches: &[Match]) -> Self {
+        self.on(
+            &format!("MATCH \"{}\" \"{}\" \"{}\"", db, strat, word),
+            MockReply::matches(matches),
+        )
+    }
+
+    pub fn show_db(self, dbs: &[Database]) -> Self {
+        self.on("SHOW DATABASES", MockReply::databases(dbs))
+    }
+
+    pub fn show_strat(self, strats: &[Strategy]) -> Self {
+        self.on("SHOW STRATEGIES", MockReply::strategies(strats))
+    }
+
+    pub fn show_info<S: AsRef<str>>(self, db: &str, lines: &[S]) -> Self {
+        self.on(
+            &format!("SHOW INFO \"{}\"", db),
+            MockReply::text_block(112, lines),
+        )
+    }
+
+    pub fn show_server<S: AsRef<str>>(self, lines: &[S]) -> Self {
+        self.on("SHOW SERVER", MockReply::text_block(114, lines))
+    }
+
+    /// Binds on `127.0.0.1:0` and serves every client on its own thread.
+    pub fn start(self) -> std::io::Result<MockHandle> {
+        let listener = TcpListener::bind(("127.0.0.1", 0))?;
+        let addr = listener.local_addr()?;
+        let script = Arc::new(Mutex::new(self.script));
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let script = Arc::clone(&script);
+            let stopped = Arc::clone(&stopped);
+
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stopped.load(Ordering::SeqCst) {
+                        break;
+                    }
+
+                    if let Ok(stream) = stream {
+                        let script = Arc::clone(&script);
+                        thread::spawn(move || serve(stream, script));
+                    }
+                }
+            })
+        };
+
+        Ok(MockHandle {
+            addr,
+            script,
+            stopped,
+            thread: Some(thread),
+        })
+    }
+}
+
+fn play(stream: &mut TcpStream, reply: &MockReply) -> std::io::Result<bool> {
+    for action in reply.0.iter() {
+        match action {
+            Action::Line(line) => {
+                write!(stream, "{}\r\n", line)?;
+                stream.flush()?;
+            }
+            Action::Sleep(duration) => thread::sleep(*duration),
+            Action::Disconnect => {
+                stream.shutdown(Shutdown::Both)?;
+                return Ok(false);
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+fn serve(mut stream: TcpStream, script: Arc<Mutex<Script>>) -> std::io::Result<()> {
+    // Replies are written line by line, don't let them wait for each other
+    stream.set_nodelay(true)?;
+
+    let banner = script.lock().unwrap().banner.clone();
+    if !play(&mut stream, &banner)? {
+        return Ok(());
+    }
+
+    let mut input = BufReader::new(stream.try_clone()?);
+    let mut line = String::new();
+
+    while input.read_line(&mut line)? > 0 {
+        let command = line.trim_end_matches(&['\r', '\n'][..]);
+
+        if command.eq_ignore_ascii_case("QUIT") {
+            write!(stream, "221 bye\r\n")?;
+            break;
+        }
+
+        let reply = script.lock().unwrap().reply_for(command);
+        if !play(&mut stream, &reply)? {
+            break;
+        }
+
+        line.clear();
+    }
+
+    Ok(())
+}
+
+/// A running mock server, stopped when dropped.
+pub struct MockHandle {
+    addr: SocketAddr,
+    script: Arc<Mutex<Script>>,
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockHandle {
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn connect(&self) -> std::io::Result<TcpStream> {
+        TcpStream::connect(self.addr)
+    }
+
+    /// Every command received so far, from all clients.
+    pub fn received(&self) -> Vec<String> {
+        self.script.lock().unwrap().received.clone()
+    }
+}
+
+impl Drop for MockHandle {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+
+        // Wake up the accept loop so that it notices
+        TcpStream::connect(self.addr).ok();
+
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+/// A definition from `name`, described by `desc`.
+pub fn definition(name: &str, desc: &str, text: &[&str]) -> Definition {
+    Definition {
+        source: Database {
+            name: String::from(name),
+            desc: String::from(desc),
+        },
+        text: text.iter().map(|l| l.to_string()).collect(),
+    }
+}
+
+/// A one line definition from WordNet.
+pub fn wordnet(text: &str) -> Definition {
+    definition("wn", "WordNet", &[text])
+}
+
+/// A connection to `server`, past its banner. Panics on errors.
+pub fn connect(server: &MockHandle) -> DICTConnection {
+    let mut conn = DICTConnection::new(server.connect().unwrap()).unwrap();
+    conn.start().unwrap();
+    conn
+}
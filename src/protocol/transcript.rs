@@ -0,0 +1,282 @@
+//! Recording and replaying of the raw bytes exchanged with a server.
+//!
+//! A transcript is a text file with one entry per line:
+//!
+//! ```text
+//! # redict transcript
+//! 0 < 220 dict.org dictd <auth.mime> <42@dict.org>\r\n
+//! 3 > CLIENT "redict"\r\n
+//! 27 < 250 ok\r\n
+//! ```
+//!
+//! The first field is the number of milliseconds since the recording
+//! started, then `<` for bytes received and `>` for bytes sent, and
+//! finally the bytes themselves, escaped so that they fit on one line.
+use crate::escape::{escape_bytes, unescape_bytes};
+use std::error::Error;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Cursor, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Direction {
+    Received,
+    Sent,
+}
+
+impl Direction {
+    fn marker(self) -> char {
+        match self {
+            Direction::Received => '<',
+            Direction::Sent => '>',
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Entry {
+    pub at: Duration,
+    pub direction: Direction,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum TranscriptError {
+    ReadWriteError(std::io::Error),
+    MalformedEntry(usize),
+}
+
+impl Display for TranscriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ReadWriteError(e) => write!(f, "{}", e),
+            Self::MalformedEntry(line) => write!(f, "Malformed entry at line {}", line),
+        }
+    }
+}
+
+impl Error for TranscriptError {}
+
+impl From<std::io::Error> for TranscriptError {
+    fn from(src: std::io::Error) -> Self {
+        TranscriptError::ReadWriteError(src)
+    }
+}
+
+/// Writes transcript entries, shared between the reading and writing halves
+/// of a connection.
+#[derive(Clone)]
+pub struct Recorder {
+    start: Instant,
+    output: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl Recorder {
+    pub fn new<W: Write + Send + 'static>(mut output: W) -> std::io::Result<Self> {
+        writeln!(output, "# redict transcript")?;
+
+        Ok(Recorder {
+            start: Instant::now(),
+            output: Arc::new(Mutex::new(Box::new(output))),
+        })
+    }
+
+    pub fn create<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Self::new(File::create(path)?)
+    }
+
+    fn record(&self, direction: Direction, bytes: &[u8]) -> std::io::Result<()> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+
+        let mut output = self.output.lock().unwrap();
+        writeln!(
+            output,
+            "{} {} {}",
+            self.start.elapsed().as_millis(),
+            direction.marker(),
+            escape_bytes(bytes)
+        )?;
+        output.flush()
+    }
+
+    pub fn reader<R: Read>(&self, inner: R) -> Recording<R> {
+        Recording {
+            inner,
+            recorder: self.clone(),
+        }
+    }
+
+    pub fn writer<W: Write>(&self, inner: W) -> Recording<W> {
+        Recording {
+            inner,
+            recorder: self.clone(),
+        }
+    }
+}
+
+/// A reader or a writer whose traffic goes to a `Recorder`.
+pub struct Recording<T> {
+    inner: T,
+    recorder: Recorder,
+}
+
+impl<R: Read> Read for Recording<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.recorder.record(Direction::Received, &buf[..n])?;
+        Ok(n)
+    }
+}
+
+impl<W: Write> Write for Recording<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.recorder.record(Direction::Sent, &buf[..n])?;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Transcript {
+    pub entries: Vec<Entry>,
+}
+
+impl Transcript {
+    pub fn from_reader<T: BufRead>(r: T) -> Result<Self, TranscriptError> {
+        let mut entries = Vec::new();
+
+        for (nr, line) in r.lines().enumerate() {
+            let line = line?;
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let malformed = || TranscriptError::MalformedEntry(nr + 1);
+            let mut parts = line.splitn(3, ' ');
+
+            let at = parts
+                .next()
+                .and_then(|ms| ms.parse::<u64>().ok())
+                .map(Duration::from_millis)
+                .ok_or_else(malformed)?;
+            let direction = match parts.next() {
+                Some("<") => Direction::Received,
+                Some(">") => Direction::Sent,
+                _ => return Err(malformed()),
+            };
+            let bytes = unescape_bytes(parts.next().unwrap_or("")).ok_or_else(malformed)?;
+
+            entries.push(Entry {
+                at,
+                direction,
+                bytes,
+            });
+        }
+
+        Ok(Transcript { entries })
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, TranscriptError> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    fn bytes(&self, direction: Direction) -> Vec<u8> {
+        self.entries
+            .iter()
+            .filter(|e| e.direction == direction)
+            .flat_map(|e| e.bytes.iter().copied())
+            .collect()
+    }
+
+    /// Everything the server sent, in order.
+    pub fn received(&self) -> Vec<u8> {
+        self.bytes(Direction::Received)
+    }
+
+    /// Everything the client sent, in order.
+    pub fn sent(&self) -> Vec<u8> {
+        self.bytes(Direction::Sent)
+    }
+
+    pub fn replay(&self) -> Replay {
+        Replay {
+            input: Cursor::new(self.received()),
+        }
+    }
+}
+
+/// Feeds back what the server sent during a recording, regardless of what
+/// is written to it.
+pub struct Replay {
+    input: Cursor<Vec<u8>>,
+}
+
+impl Read for Replay {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.input.read(buf)
+    }
+}
+
+impl BufRead for Replay {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.input.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.input.consume(amt)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn record_and_parse() {
+        let buffer: Arc<Mutex<Vec<u8>>> = Arc::new(Mutex::new(Vec::new()));
+
+        struct Shared(Arc<Mutex<Vec<u8>>>);
+        impl Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let recorder = Recorder::new(Shared(Arc::clone(&buffer))).unwrap();
+
+        let mut input = recorder.reader(&b"220 hello\r\n"[..]);
+        let mut output = recorder.writer(Vec::new());
+
+        let mut line = String::new();
+        BufReader::new(&mut input).read_line(&mut line).unwrap();
+        write!(output, "QUIT\r\n").unwrap();
+
+        let text = buffer.lock().unwrap().clone();
+        let transcript = Transcript::from_reader(&text[..]).unwrap();
+
+        assert_eq!(transcript.received(), b"220 hello\r\n".to_vec());
+        assert_eq!(transcript.sent(), b"QUIT\r\n".to_vec());
+        assert_eq!(transcript.entries[1].direction, Direction::Sent);
+    }
+
+    #[test]
+    fn malformed_entry() {
+        match Transcript::from_reader(&b"# comment\n0 < ok\nfoo\n"[..]) {
+            Err(TranscriptError::MalformedEntry(3)) => {}
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+}
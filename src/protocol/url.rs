@@ -135,7 +135,7 @@ impl DICTUrl {
             .host_str()
             .ok_or(DICTUrlError::MissingHost)?
             .to_string();
-        let port: u16 = raw_url.port().or(Some(2628)).unwrap();
+        let port: u16 = raw_url.port().unwrap_or(2628);
         let access_method = DICTUrlAccess::from_str(raw_url.path())?;
 
         Ok(DICTUrl {
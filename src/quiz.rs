@@ -0,0 +1,269 @@
+use crate::xdg;
+
+use dictproto::escape::{escape, unescape};
+
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// $XDG_DATA_HOME/redict/quiz
+pub fn default_path() -> Option<PathBuf> {
+    xdg::data_dir().map(|dir| dir.join("quiz"))
+}
+
+/// Seconds since the epoch
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// A word to remember, scheduled with SM-2
+#[derive(Debug, PartialEq)]
+pub struct Card {
+    pub word: String,
+    pub database: String,
+    ease: f64,
+    /// In days
+    interval: u64,
+    repetitions: u32,
+    /// When to review it next, in seconds since the epoch
+    pub due: u64
+}
+
+impl Card {
+    pub fn new(word: String, database: String, now: u64) -> Self {
+        Card {
+            word,
+            database,
+            ease: 2.5,
+            interval: 0,
+            repetitions: 0,
+            due: now
+        }
+    }
+
+    // The fields are escaped and separated by tabs
+    fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        match fields[..] {
+            [word, database, ease, interval, repetitions, due] => Some(Card {
+                word: unescape(word),
+                database: unescape(database),
+                ease: ease.parse().ok()?,
+                interval: interval.parse().ok()?,
+                repetitions: repetitions.parse().ok()?,
+                due: due.parse().ok()?
+            }),
+            _ => None
+        }
+    }
+
+    /// Schedules the next review, after recalling the word with quality
+    /// from 0 (forgotten) to 5 (perfect)
+    pub fn grade(&mut self, quality: u8, now: u64) {
+        let quality = quality.min(5);
+
+        if quality >= 3 {
+            self.interval = match self.repetitions {
+                0 => 1,
+                1 => 6,
+                _ => (self.interval as f64 * self.ease).round() as u64
+            };
+            self.repetitions += 1;
+        } else {
+            self.repetitions = 0;
+            self.interval = 1;
+        }
+
+        let miss = f64::from(5 - quality);
+        self.ease = (self.ease + 0.1 - miss * (0.08 + miss * 0.02)).max(1.3);
+        self.due = now + self.interval * DAY;
+    }
+}
+
+/// The cards of all the words bookmarked, saved after each review
+pub struct Deck {
+    cards: Vec<Card>,
+    path: Option<PathBuf>
+}
+
+impl Deck {
+    pub fn new() -> Self {
+        Deck {
+            cards: Vec::new(),
+            path: None
+        }
+    }
+
+    pub fn load(path: PathBuf) -> Self {
+        let mut deck = Self::new();
+
+        if let Ok(content) = fs::read_to_string(&path) {
+            deck.cards.extend(content.lines().filter_map(Card::parse));
+        }
+        deck.path = Some(path);
+
+        deck
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(())
+        };
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let content: String = self.cards.iter()
+            .map(|c| format!("{}\t{}\t{}\t{}\t{}\t{}\n", escape(&c.word), escape(&c.database), c.ease, c.interval,
+                c.repetitions, c.due))
+            .collect();
+        fs::write(path, content)
+    }
+
+    /// Adds a card for each word that has none, and drops those of words
+    /// no longer in any list
+    pub fn sync<'a, I>(&mut self, words: I, now: u64)
+    where I: Iterator<Item = (&'a str, &'a str)> {
+        let words: Vec<(&str, &str)> = words.collect();
+        self.cards.retain(|c| words.iter().any(|&(word, database)| c.word == word && c.database == database));
+
+        for (word, database) in words {
+            if !self.cards.iter().any(|c| c.word == word && c.database == database) {
+                self.cards.push(Card::new(word.to_owned(), database.to_owned(), now));
+            }
+        }
+    }
+
+    pub fn card(&self, index: usize) -> &Card {
+        &self.cards[index]
+    }
+
+    /// When the next card is due
+    pub fn next_due(&self) -> Option<u64> {
+        self.cards.iter().map(|c| c.due).min()
+    }
+}
+
+/// The cards due, reviewed one after the other
+pub struct Review {
+    queue: VecDeque<usize>,
+    pub revealed: bool,
+    pub reviewed: usize
+}
+
+impl Review {
+    pub fn new(deck: &Deck, now: u64) -> Self {
+        let mut due: Vec<usize> = (0..deck.cards.len())
+            .filter(|&i| deck.cards[i].due <= now)
+            .collect();
+        due.sort_by_key(|&i| deck.cards[i].due);
+
+        Review {
+            queue: due.into(),
+            revealed: false,
+            reviewed: 0
+        }
+    }
+
+    /// Index of the card to review
+    pub fn current(&self) -> Option<usize> {
+        self.queue.front().copied()
+    }
+
+    pub fn left(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Grades the current card. Forgotten ones come back at the end.
+    pub fn grade(&mut self, deck: &mut Deck, quality: u8, now: u64) -> io::Result<()> {
+        let i = match self.queue.pop_front() {
+            Some(i) => i,
+            None => return Ok(())
+        };
+
+        deck.cards[i].grade(quality, now);
+        if quality < 3 {
+            self.queue.push_back(i);
+        }
+        self.revealed = false;
+        self.reviewed += 1;
+
+        deck.save()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn sm2_intervals() {
+        let mut card = Card::new(String::from("tie"), String::from("wn"), 0);
+
+        card.grade(5, 0);
+        assert_eq!((card.interval, card.due), (1, DAY));
+        card.grade(4, 0);
+        assert_eq!(card.interval, 6);
+        card.grade(4, 0);
+        // The ease went up once, by 0.1
+        assert_eq!(card.interval, 16);
+
+        card.grade(1, 0);
+        assert_eq!((card.interval, card.repetitions), (1, 0));
+        assert!(card.ease < 2.6);
+    }
+
+    #[test]
+    fn minimal_ease() {
+        let mut card = Card::new(String::from("tie"), String::from("wn"), 0);
+        for _ in 0..10 {
+            card.grade(0, 0);
+        }
+
+        assert!((card.ease - 1.3).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn review_due_cards() {
+        let mut deck = Deck::new();
+        deck.sync(vec![("tie", "wn"), ("tin", "wn"), ("tie", "wn")].into_iter(), 0);
+        assert_eq!(deck.cards.len(), 2);
+        deck.cards[1].due = 10 * DAY;
+
+        let mut review = Review::new(&deck, 0);
+        assert_eq!(review.left(), 1);
+
+        // Forgotten, it comes back
+        review.grade(&mut deck, 2, 0).unwrap();
+        assert_eq!(review.current(), Some(0));
+        review.grade(&mut deck, 4, 0).unwrap();
+        assert_eq!(review.current(), None);
+        assert_eq!(review.reviewed, 2);
+        assert_eq!(deck.next_due(), Some(DAY));
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path = env::temp_dir().join(format!("redict-quiz-{}", std::process::id()));
+        fs::remove_file(&path).ok();
+
+        let mut deck = Deck::load(path.clone());
+        deck.sync(vec![("tie", "wn"), ("a\tb\\n", "wn")].into_iter(), 0);
+        let mut review = Review::new(&deck, 0);
+        review.grade(&mut deck, 5, 0).unwrap();
+
+        let loaded = Deck::load(path.clone());
+        fs::remove_file(&path).ok();
+
+        assert_eq!(loaded.cards, deck.cards);
+    }
+}
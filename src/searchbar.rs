@@ -1,55 +1,162 @@
-use std::default::Default;
-
-use tui::{Frame, backend::Backend, style::Style, widgets::Borders};
+use tui::{Frame, backend::Backend, style::Style};
 use tui::style::Color;
 use tui::widgets::{Paragraph, Block};
 use tui::text::{Span, Spans};
 use tui::layout::Rect;
 use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use std::mem;
+
+/// Shown where the text goes on past the edges
+const ELLIPSIS: &str = "…";
+
+/// Killed texts kept for yanking
+const KILL_RING_SIZE: usize = 16;
+/// Edits that can be undone
+const UNDO_SIZE: usize = 100;
 
 pub enum CursorDirection {
     Left,
     Right,
     First,
-    Last
+    Last,
+    WordLeft,
+    WordRight
+}
+
+// What the last edit was, for those that follow it
+#[derive(Clone, Copy, PartialEq)]
+enum LastEdit {
+    Insert,
+    Kill,
+    /// The graphemes yanked, and the kill they come from
+    Yank { start: usize, kill: usize }
 }
 
+fn is_word(grapheme: &str) -> bool {
+    grapheme.chars().next().map_or(false, char::is_alphanumeric)
+}
+
+fn is_blank(grapheme: &str) -> bool {
+    grapheme.chars().all(char::is_whitespace)
+}
+
+#[derive(Default)]
 pub struct SearchBar {
     text: String,
-    cursor: usize
+    cursor: usize,
+    /// The latest last
+    kills: Vec<String>,
+    /// Text and cursor before each edit
+    undo_stack: Vec<(String, usize)>,
+    /// Text and cursor before each undo
+    redo_stack: Vec<(String, usize)>,
+    last_edit: Option<LastEdit>,
+    /// The first grapheme shown
+    scroll: usize
 }
 
-impl Default for SearchBar {
-    fn default() -> Self {
-        Self {
-            text: String::new(),
-            cursor: 0
-        }
-    }
+// Columns taken by the ellipsis on the left
+fn margin(first: usize) -> usize {
+    if first > 0 { 1 } else { 0 }
 }
 
 impl SearchBar {
-    pub fn draw<B: Backend>(&self, f: &mut Frame<B>, rect: Rect, block: Block) {
+    /// Draws the text, scrolled for the cursor to stay in view
+    pub fn draw<B: Backend>(&mut self, f: &mut Frame<B>, rect: Rect, block: Block) {
+        let width = block.inner(rect).width as usize;
+        self.scroll = self.scroll_for(self.scroll, width);
+
+        let len = self.graphemes_nr();
+        let end = self.view_end(self.scroll, width);
+        let at_cursor = if self.cursor < len { self.slice(self.cursor, self.cursor + 1) } else { " " };
+        let ellipsis = Span::styled(ELLIPSIS, Style::default().fg(Color::DarkGray));
+
+        let mut spans = Vec::new();
+        if self.scroll > 0 {
+            spans.push(ellipsis.clone());
+        }
+        spans.push(Span::raw(self.slice(self.scroll, self.cursor)));
+        spans.push(Span::styled(at_cursor, Style::default().fg(Color::Black).bg(Color::White)));
+        spans.push(Span::raw(self.slice((self.cursor + 1).min(len), end)));
+        if end < len {
+            spans.push(ellipsis);
+        }
 
-        let (before_cursor, at_cursor, after_cursor) : (&str, &str, &str) = match self.cursor_indices() {
-            (Some(cursor_index), Some(after_cursor_index)) => {
-                (self.text.get(..cursor_index).unwrap(),
-                self.text.get(cursor_index..after_cursor_index).unwrap(),
-                self.text.get(after_cursor_index..).unwrap())
-            },
-            (Some(cursor_index), None) => (self.text.get(..cursor_index).unwrap(), self.text.get(cursor_index..).unwrap(), ""),
-            (None, None) => (self.text.as_ref(), " ", ""),
-            _ => unreachable!()
+        let par = Paragraph::new(Spans::from(spans)).block(block);
+        f.render_widget(par, rect);
+    }
+
+    // Columns taken by each grapheme
+    fn widths(&self) -> Vec<usize> {
+        self.text.graphemes(true).map(UnicodeWidthStr::width).collect()
+    }
+
+    // The cursor is as wide as the grapheme under it, and shown after the
+    // text at the end
+    fn cursor_width(&self, widths: &[usize]) -> usize {
+        widths.get(self.cursor).map_or(1, |&w| w.max(1))
+    }
+
+    // The first grapheme to show in width columns, after scroll was: the
+    // cursor stays in view, and as much text as possible is shown
+    fn scroll_for(&self, scroll: usize, width: usize) -> usize {
+        let widths = self.widths();
+        let cursor_width = self.cursor_width(&widths);
+
+        // From first to the cursor, with room for an ellipsis after it
+        let fits_cursor = |first: usize| {
+            let more = if self.cursor + 1 < widths.len() { 1 } else { 0 };
+            margin(first) + widths[first..self.cursor].iter().sum::<usize>() + cursor_width + more <= width
         };
+        // From first to the end, with a column left for the cursor
+        let fits_all = |first: usize| margin(first) + widths[first..].iter().sum::<usize>() < width;
 
-        let spans = Spans::from(vec![
-            Span::raw(before_cursor),
-            Span::styled(at_cursor, Style::default().fg(Color::Black).bg(Color::White)),
-            Span::raw(after_cursor)
-        ]);
+        let mut first = scroll.min(self.cursor);
+        while first < self.cursor && !fits_cursor(first) {
+            first += 1;
+        }
+        while first > 0 && fits_all(first - 1) {
+            first -= 1;
+        }
+        first
+    }
 
-        let par = Paragraph::new(spans).block(block);
-        f.render_widget(par, rect);
+    // The end of the graphemes shown from first in width columns
+    fn view_end(&self, first: usize, width: usize) -> usize {
+        let widths = self.widths();
+        if self.cursor >= widths.len() {
+            return widths.len();
+        }
+
+        let used = margin(first) + widths[first..self.cursor].iter().sum::<usize>() + self.cursor_width(&widths);
+        let room = width.saturating_sub(used);
+        let after = &widths[self.cursor + 1..];
+        if after.iter().sum::<usize>() <= room {
+            return widths.len();
+        }
+
+        // With room for the ellipsis
+        let mut taken = 0;
+        let shown = after.iter()
+            .take_while(|&&w| {
+                taken += w;
+                taken < room
+            })
+            .count();
+        self.cursor + 1 + shown
+    }
+
+    /// The column of a grapheme in the search bar as last drawn
+    pub fn column(&self, grapheme: usize) -> usize {
+        let widths = self.widths();
+        let end = grapheme.min(widths.len());
+        if end < self.scroll {
+            return 0;
+        }
+
+        margin(self.scroll) + widths[self.scroll..end].iter().sum::<usize>()
     }
 
     fn cursor_indices(&self) -> (Option<usize>, Option<usize>) {
@@ -73,8 +180,61 @@ impl SearchBar {
         &self.text
     }
 
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    fn byte_index(&self, grapheme: usize) -> usize {
+        self.text.grapheme_indices(true)
+            .nth(grapheme)
+            .map_or(self.text.len(), |(index, _)| index)
+    }
+
+    /// Text between two grapheme positions
+    pub fn slice(&self, start: usize, end: usize) -> &str {
+        &self.text[self.byte_index(start)..self.byte_index(end)]
+    }
+
+    /// Start of the word that ends at the cursor
+    pub fn word_start(&self) -> usize {
+        let graphemes: Vec<&str> = self.text.graphemes(true).take(self.cursor).collect();
+
+        graphemes.iter()
+            .rposition(|g| g.chars().all(char::is_whitespace))
+            .map_or(0, |pos| pos + 1)
+    }
+
+    // Skipping the graphemes out of words, then those in one
+    fn word_left(&self, in_word: fn(&str) -> bool) -> usize {
+        let graphemes: Vec<&str> = self.text.graphemes(true).take(self.cursor).collect();
+        let mut i = graphemes.len();
+
+        while i > 0 && !in_word(graphemes[i - 1]) {
+            i -= 1;
+        }
+        while i > 0 && in_word(graphemes[i - 1]) {
+            i -= 1;
+        }
+        i
+    }
+
+    fn word_right(&self, in_word: fn(&str) -> bool) -> usize {
+        let graphemes: Vec<&str> = self.text.graphemes(true).collect();
+        let mut i = self.cursor;
+
+        while i < graphemes.len() && !in_word(graphemes[i]) {
+            i += 1;
+        }
+        while i < graphemes.len() && in_word(graphemes[i]) {
+            i += 1;
+        }
+        i
+    }
+
     // Cursor manipulation
     pub fn move_cursor(&mut self, dir: CursorDirection) {
+        self.last_edit = None;
+
         match dir {
             CursorDirection::Left => {
                 if self.cursor > 0 {
@@ -91,45 +251,190 @@ impl SearchBar {
             },
             CursorDirection::Last => {
                 self.cursor = self.graphemes_nr();
+            },
+            CursorDirection::WordLeft => {
+                self.cursor = self.word_left(is_word);
+            },
+            CursorDirection::WordRight => {
+                self.cursor = self.word_right(is_word);
             }
         }
     }
 
+    // Keeps the text as it is before an edit, to undo it
+    fn save(&mut self) {
+        self.undo_stack.push((self.text.clone(), self.cursor));
+        let len = self.undo_stack.len();
+        self.undo_stack.drain(..len.saturating_sub(UNDO_SIZE));
+        self.redo_stack.clear();
+    }
+
+    // Replaces the graphemes between start and end, with the cursor after
+    // the replacement
+    fn splice(&mut self, start: usize, end: usize, with: &str) {
+        let range = self.byte_index(start)..self.byte_index(end);
+        self.text.replace_range(range, with);
+        self.cursor = start + with.graphemes(true).count();
+    }
+
     // Text manipulation
+    /// Inserts c at the cursor. Characters typed in a row are undone
+    /// together.
     pub fn edit(&mut self, c: char) {
+        if self.last_edit != Some(LastEdit::Insert) {
+            self.save();
+        }
+
         if let (Some(cursor_start), _) = self.cursor_indices() {
             self.text.insert(cursor_start, c);
         } else {
             self.text.push(c);
         }
         self.move_cursor(CursorDirection::Right);
+        self.last_edit = Some(LastEdit::Insert);
     }
 
     pub fn delete(&mut self) {
         if self.cursor == 0 { return; }
 
-        self.move_cursor(CursorDirection::Left);
-        match self.cursor_indices() {
-            (Some(cursor_start), Some(after_cursor_start)) => {
-                self.text.replace_range(cursor_start..after_cursor_start, "");
-            },
-            (Some(cursor_start), None) => {
-                self.text.replace_range(cursor_start.., "");
+        self.save();
+        self.splice(self.cursor - 1, self.cursor, "");
+        self.last_edit = None;
+    }
+
+    /// Deletes the character under the cursor
+    pub fn delete_forward(&mut self) {
+        if self.cursor < self.graphemes_nr() {
+            self.save();
+            self.splice(self.cursor, self.cursor + 1, "");
+        }
+        self.last_edit = None;
+    }
+
+    /// Swaps the characters around the cursor, or the last two at the end
+    pub fn transpose(&mut self) {
+        let len = self.graphemes_nr();
+        if len < 2 || self.cursor == 0 {
+            return;
+        }
+
+        let at = self.cursor.min(len - 1);
+        let swapped = format!("{}{}", self.slice(at, at + 1), self.slice(at - 1, at));
+        self.save();
+        self.splice(at - 1, at + 1, &swapped);
+        self.last_edit = None;
+    }
+
+    // Kills in a row make one, for a single yank
+    fn kill(&mut self, start: usize, end: usize) {
+        if start == end {
+            return;
+        }
+
+        let killed = self.slice(start, end).to_owned();
+        match self.kills.last_mut() {
+            Some(last) if self.last_edit == Some(LastEdit::Kill) => {
+                if start < self.cursor {
+                    last.insert_str(0, &killed);
+                } else {
+                    last.push_str(&killed);
+                }
             },
-            (None, None) => { self.text.pop(); }
-            _ => { return; }
+            _ => {
+                self.kills.push(killed);
+                let len = self.kills.len();
+                self.kills.drain(..len.saturating_sub(KILL_RING_SIZE));
+            }
+        }
+
+        self.save();
+        self.splice(start, end, "");
+        self.last_edit = Some(LastEdit::Kill);
+    }
+
+    /// Kills from the cursor to the end
+    pub fn kill_to_end(&mut self) {
+        self.kill(self.cursor, self.graphemes_nr());
+    }
+
+    /// Kills the word before the cursor, up to a blank
+    pub fn kill_word_backward(&mut self) {
+        self.kill(self.word_left(|g| !is_blank(g)), self.cursor);
+    }
+
+    fn insert_kill(&mut self, kill: usize) {
+        let start = self.cursor;
+        let text = self.kills[kill].clone();
+        self.splice(start, start, &text);
+        self.last_edit = Some(LastEdit::Yank { start, kill });
+    }
+
+    /// Inserts the text killed last
+    pub fn yank(&mut self) {
+        if self.kills.is_empty() {
+            return;
+        }
+
+        self.save();
+        self.insert_kill(self.kills.len() - 1);
+    }
+
+    /// Right after a yank, replaces the text yanked with the one killed
+    /// before it
+    pub fn yank_pop(&mut self) {
+        let (start, kill) = match self.last_edit {
+            Some(LastEdit::Yank { start, kill }) => (start, kill),
+            _ => return
+        };
+
+        self.save();
+        self.splice(start, self.cursor, "");
+        let previous = if kill == 0 { self.kills.len() - 1 } else { kill - 1 };
+        self.insert_kill(previous);
+    }
+
+    pub fn undo(&mut self) {
+        if let Some((text, cursor)) = self.undo_stack.pop() {
+            self.redo_stack.push((mem::replace(&mut self.text, text), self.cursor));
+            self.cursor = cursor;
+        }
+        self.last_edit = None;
+    }
+
+    pub fn redo(&mut self) {
+        if let Some((text, cursor)) = self.redo_stack.pop() {
+            self.undo_stack.push((mem::replace(&mut self.text, text), self.cursor));
+            self.cursor = cursor;
         }
+        self.last_edit = None;
+    }
+
+    /// Replaces the graphemes between start and end, and puts the cursor
+    /// after the replacement
+    pub fn replace(&mut self, start: usize, end: usize, with: &str) {
+        self.save();
+        self.splice(start, end, with);
+        self.last_edit = None;
     }
 
     pub fn set_text<T>(&mut self, text: &T)
     where T: ToString {
-        self.text = text.to_string();
+        let text = text.to_string();
+        if text != self.text {
+            self.save();
+        }
+        self.text = text;
         self.cursor = 0;
+        self.last_edit = None;
     }
 
     pub fn clear(&mut self) {
+        if !self.text.is_empty() {
+            self.save();
+        }
         self.text.clear();
         self.cursor = 0;
+        self.last_edit = None;
     }
 }
 
@@ -203,6 +508,20 @@ mod test {
         // Only assert aliveness
     }
 
+    #[test]
+    fn delete_forward() {
+        let mut search = SearchBar::default();
+        search.set_text(&"fĝh");
+        search.move_cursor(CursorDirection::Right);
+        search.delete_forward();
+        assert_eq!(search.text(), "fh");
+        assert_eq!(search.cursor(), 1);
+
+        search.move_cursor(CursorDirection::Last);
+        search.delete_forward();
+        assert_eq!(search.text(), "fh");
+    }
+
     #[test]
     fn complex_grapheme_insert() {
         let mut search = SearchBar::default();
@@ -221,6 +540,231 @@ mod test {
         assert!(search.text().is_empty());
     }
 
+    #[test]
+    fn word_start() {
+        let mut search = SearchBar::default();
+        search.set_text(&"fo @ĝb");
+        assert_eq!(search.word_start(), 0);
+
+        search.move_cursor(CursorDirection::Last);
+        assert_eq!(search.word_start(), 3);
+        assert_eq!(search.slice(search.word_start(), search.cursor()), "@ĝb");
+
+        search.edit(' ');
+        assert_eq!(search.word_start(), 7);
+    }
+
+    #[test]
+    fn replace() {
+        let mut search = SearchBar::default();
+        search.set_text(&"ĝo @fd :le");
+        search.move_cursor(CursorDirection::Right);
+        search.move_cursor(CursorDirection::Right);
+        search.move_cursor(CursorDirection::Right);
+        search.move_cursor(CursorDirection::Right);
+        search.move_cursor(CursorDirection::Right);
+        search.move_cursor(CursorDirection::Right);
+
+        search.replace(3, 6, "@fd-eng-fra");
+        assert_eq!(search.text(), "ĝo @fd-eng-fra :le");
+        assert_eq!(search.cursor(), 14);
+    }
+
+    #[test]
+    fn word_moves() {
+        let mut search = SearchBar::default();
+        search.set_text(&"ĝis, la revido");
+
+        search.move_cursor(CursorDirection::WordRight);
+        assert_eq!(search.cursor(), 3);
+        search.move_cursor(CursorDirection::WordRight);
+        assert_eq!(search.cursor(), 7);
+
+        search.move_cursor(CursorDirection::Last);
+        search.move_cursor(CursorDirection::WordLeft);
+        assert_eq!(search.cursor(), 8);
+        search.move_cursor(CursorDirection::WordLeft);
+        search.move_cursor(CursorDirection::WordLeft);
+        assert_eq!(search.cursor(), 0);
+    }
+
+    #[test]
+    fn kill_to_end() {
+        let mut search = SearchBar::default();
+        // A g and a combining circumflex
+        search.set_text(&"g\u{302}is @wn");
+        search.move_cursor(CursorDirection::WordRight);
+        assert_eq!(search.cursor(), 3);
+        search.kill_to_end();
+        assert_eq!(search.text(), "g\u{302}is");
+
+        // Nothing left to kill
+        search.kill_to_end();
+        search.move_cursor(CursorDirection::First);
+        search.yank();
+        assert_eq!(search.text(), " @wng\u{302}is");
+    }
+
+    #[test]
+    fn kill_word_backward() {
+        let mut search = SearchBar::default();
+        search.set_text(&"tie @fd-eng-fra ");
+        search.move_cursor(CursorDirection::Last);
+        search.kill_word_backward();
+        assert_eq!(search.text(), "tie ");
+
+        // Killed in a row, yanked as one
+        search.kill_word_backward();
+        assert_eq!(search.text(), "");
+        search.yank();
+        assert_eq!(search.text(), "tie @fd-eng-fra ");
+    }
+
+    #[test]
+    fn yank_pop() {
+        let mut search = SearchBar::default();
+        search.set_text(&"a b");
+        search.move_cursor(CursorDirection::Last);
+        search.kill_word_backward();
+        search.move_cursor(CursorDirection::Left);
+        search.kill_word_backward();
+        assert_eq!(search.text(), " ");
+
+        search.yank();
+        assert_eq!(search.text(), "a ");
+        search.yank_pop();
+        assert_eq!(search.text(), "b ");
+        assert_eq!(search.cursor(), 1);
+        // Around the ring
+        search.yank_pop();
+        assert_eq!(search.text(), "a ");
+
+        // Only right after a yank
+        search.move_cursor(CursorDirection::Last);
+        search.yank_pop();
+        assert_eq!(search.text(), "a ");
+    }
+
+    #[test]
+    fn transpose() {
+        let mut search = SearchBar::default();
+        search.set_text(&"ĝih");
+        search.transpose();
+        assert_eq!(search.text(), "ĝih");
+
+        search.move_cursor(CursorDirection::Right);
+        search.transpose();
+        assert_eq!(search.text(), "iĝh");
+        assert_eq!(search.cursor(), 2);
+
+        // The last two at the end
+        search.move_cursor(CursorDirection::Last);
+        search.transpose();
+        assert_eq!(search.text(), "ihĝ");
+        assert_eq!(search.cursor(), 3);
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut search = SearchBar::default();
+        search.edit('t'); search.edit('i');
+        search.move_cursor(CursorDirection::First);
+        search.edit('ĝ');
+        search.delete_forward();
+        assert_eq!(search.text(), "ĝi");
+
+        search.undo();
+        assert_eq!(search.text(), "ĝti");
+        search.undo();
+        assert_eq!(search.text(), "ti");
+        assert_eq!(search.cursor(), 0);
+        // Typed in a row
+        search.undo();
+        assert_eq!(search.text(), "");
+        search.undo();
+
+        search.redo();
+        search.redo();
+        assert_eq!(search.text(), "ĝti");
+        assert_eq!(search.cursor(), 1);
+
+        // A new edit forgets what was undone
+        search.edit('e');
+        search.redo();
+        assert_eq!(search.text(), "ĝeti");
+    }
+
+    #[test]
+    fn fits() {
+        let mut search = SearchBar::default();
+        search.set_text(&"tie");
+        search.move_cursor(CursorDirection::Last);
+
+        assert_eq!(search.scroll_for(0, 4), 0);
+        assert_eq!(search.view_end(0, 4), 3);
+    }
+
+    #[test]
+    fn scroll_to_cursor() {
+        let mut search = SearchBar::default();
+        search.set_text(&"abcdefghij");
+        search.move_cursor(CursorDirection::Last);
+
+        // …ghij and the cursor
+        assert_eq!(search.scroll_for(0, 6), 6);
+
+        // Stays put while the cursor is in view
+        search.move_cursor(CursorDirection::Left);
+        search.move_cursor(CursorDirection::Left);
+        assert_eq!(search.scroll_for(6, 6), 6);
+        assert_eq!(search.view_end(6, 6), 10);
+
+        // The cursor then bcde…
+        search.move_cursor(CursorDirection::First);
+        assert_eq!(search.scroll_for(6, 6), 0);
+        assert_eq!(search.view_end(0, 6), 5);
+    }
+
+    #[test]
+    fn scroll_back_when_shorter() {
+        let mut search = SearchBar::default();
+        search.set_text(&"abcdefghij");
+        search.move_cursor(CursorDirection::Last);
+        assert_eq!(search.scroll_for(0, 6), 6);
+
+        search.delete(); search.delete(); search.delete(); search.delete(); search.delete();
+        assert_eq!(search.scroll_for(6, 6), 0);
+    }
+
+    #[test]
+    fn wide_graphemes() {
+        let mut search = SearchBar::default();
+        search.set_text(&"日本語の辞書");
+        search.move_cursor(CursorDirection::Last);
+
+        // …の辞書 and the cursor
+        let scroll = search.scroll_for(0, 8);
+        assert_eq!(scroll, 3);
+        search.scroll = scroll;
+        assert_eq!(search.column(4), 3);
+
+        // The cursor, on 日, then 本語…
+        search.move_cursor(CursorDirection::First);
+        assert_eq!(search.scroll_for(scroll, 8), 0);
+        assert_eq!(search.view_end(0, 8), 3);
+    }
+
+    #[test]
+    fn combining_marks() {
+        let mut search = SearchBar::default();
+        // Six columns, with an e and a combining acute twice
+        search.set_text(&"re\u{301}sume\u{301}");
+        search.move_cursor(CursorDirection::Last);
+
+        assert_eq!(search.scroll_for(0, 7), 0);
+        assert_eq!(search.scroll_for(0, 6), 2);
+    }
+
     #[test]
     fn clear() {
         let mut search = SearchBar::default();
@@ -0,0 +1,219 @@
+use crate::events::Event;
+
+use dictproto::{
+    Definition,
+    Database,
+    Strategy,
+    Match,
+    reply::Reply,
+    client::{ClientCancelHandle, DICTClient},
+    connection::{DICTError, RawAnswer}
+};
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+#[derive(Clone)]
+pub enum Request {
+    /// Opens the session, then lists databases and strategies
+    Connect,
+    Define(Database, String),
+    Match(Database, Strategy, String),
+    /// Everything shown in Info mode, with the information about the
+    /// database at that index
+    Info(usize),
+    ShowInfo(Database),
+    Raw(String)
+}
+
+/// What one command gave
+pub enum Answer {
+    Banner,
+    Definitions(Vec<Definition>),
+    Matches(Vec<Match>),
+    Databases(Vec<Database>),
+    Strategies(Vec<Strategy>),
+    ServerInfo(Vec<String>),
+    // The statistics are in the reply
+    Stats,
+    DatabaseInfo(Database, Vec<String>),
+    Raw(Vec<RawAnswer>)
+}
+
+/// The session, as left by the last command
+pub struct Session {
+    pub reconnects: usize,
+    pub active: (String, u16),
+    pub msg_id: String,
+    pub capabilities: Vec<String>
+}
+
+pub struct Outcome {
+    pub answer: Result<(Answer, Reply), DICTError>,
+    pub session: Session
+}
+
+/// Runs the commands of a server on its own thread, and sends what they
+/// give as events
+pub struct Worker {
+    requests: Sender<(usize, Request)>,
+    cancel: Arc<Mutex<Option<ClientCancelHandle>>>,
+    // The requests up to this id are not run anymore
+    dropped: Arc<AtomicUsize>
+}
+
+impl Worker {
+    pub fn spawn(server: usize, mut conn: DICTClient, events: Sender<Event>) -> Self {
+        let (requests, rx) = mpsc::channel::<(usize, Request)>();
+        let cancel = Arc::new(Mutex::new(None));
+        let dropped = Arc::new(AtomicUsize::new(0));
+
+        let handle = Arc::clone(&cancel);
+        let until = Arc::clone(&dropped);
+        thread::spawn(move || {
+            for (id, request) in rx {
+                let mut job = Job {
+                    server,
+                    id,
+                    conn: &mut conn,
+                    events: &events,
+                    cancel: &handle,
+                    dropped: &until,
+                    stopped: false
+                };
+                job.run(request);
+
+                if events.send(Event::Done(server, id)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Worker { requests, cancel, dropped }
+    }
+
+    /// Requests are run in turn, id tells their answers apart
+    pub fn send(&self, id: usize, request: Request) {
+        self.requests.send((id, request)).ok();
+    }
+
+    /// Stops the running command, and drops the requests up to id that are
+    /// still waiting
+    pub fn cancel(&self, id: usize) {
+        self.dropped.fetch_max(id, Ordering::SeqCst);
+
+        if let Some(handle) = self.cancel.lock().unwrap().take() {
+            handle.cancel();
+        }
+    }
+}
+
+struct Job<'a> {
+    server: usize,
+    id: usize,
+    conn: &'a mut DICTClient,
+    events: &'a Sender<Event>,
+    cancel: &'a Mutex<Option<ClientCancelHandle>>,
+    dropped: &'a AtomicUsize,
+    // Once cancelled, the rest of the request is dropped
+    stopped: bool
+}
+
+impl Job<'_> {
+    fn run(&mut self, request: Request) {
+        match request {
+            Request::Connect => {
+                self.command(|c| c.connect().map(|r| ((), r)), |_| Answer::Banner);
+                self.command(DICTClient::show_db, |dbs| Answer::Databases(dbs.clone()));
+                self.command(DICTClient::show_strat, |strats| Answer::Strategies(strats.clone()));
+            },
+            Request::Define(db, word) => {
+                self.command(|c| c.define(db, word), |defs| Answer::Definitions(defs.clone()));
+            },
+            Request::Match(db, strat, word) => {
+                self.command(|c| c.match_db(db, strat, word), |m| Answer::Matches(m.clone()));
+            },
+            Request::Info(selected) => {
+                self.command(DICTClient::show_server, |text| Answer::ServerInfo(text.clone()));
+                self.command(|c| c.status().map(|r| ((), r)), |_| Answer::Stats);
+                let dbs = self.command(DICTClient::show_db, |dbs| Answer::Databases(dbs.clone()));
+                self.command(DICTClient::show_strat, |strats| Answer::Strategies(strats.clone()));
+
+                let db = dbs.and_then(|dbs| dbs.get(selected).or_else(|| dbs.first()).cloned());
+                if let Some(db) = db {
+                    self.show_info(db);
+                }
+            },
+            Request::ShowInfo(db) => {
+                self.show_info(db);
+            },
+            Request::Raw(line) => {
+                self.command(|c| raw_command(c, &line), |answers| Answer::Raw(answers.clone()));
+            }
+        }
+    }
+
+    fn show_info(&mut self, db: Database) {
+        let info_db = db.clone();
+        self.command(|c| c.show_info(db), |text| Answer::DatabaseInfo(info_db, text.clone()));
+    }
+
+    /// Runs one command and sends its outcome. Returns its value when it
+    /// succeeded.
+    fn command<T, F, A>(&mut self, f: F, answer: A) -> Option<T>
+    where F: FnOnce(&mut DICTClient) -> Result<(T, Reply), DICTError>,
+          A: FnOnce(&T) -> Answer {
+        if self.stopped {
+            return None;
+        }
+
+        // Checked once the handle is there, not to miss a cancellation made
+        // meanwhile
+        *self.cancel.lock().unwrap() = Some(self.conn.cancel_handle());
+        if self.id <= self.dropped.load(Ordering::SeqCst) {
+            self.cancel.lock().unwrap().take();
+            self.stopped = true;
+            return None;
+        }
+        let result = f(self.conn);
+        self.cancel.lock().unwrap().take();
+
+        let (value, answer) = match result {
+            Ok((value, reply)) => {
+                let answer = answer(&value);
+                (Some(value), Ok((answer, reply)))
+            },
+            Err(e) => {
+                self.stopped = matches!(e, DICTError::Cancelled);
+                (None, Err(e))
+            }
+        };
+
+        let (host, port) = self.conn.active_server();
+        let session = Session {
+            reconnects: self.conn.reconnect_attempts(),
+            active: (host.to_owned(), port),
+            msg_id: self.conn.msg_id().to_owned(),
+            capabilities: self.conn.capabilities().to_vec()
+        };
+
+        let outcome = Outcome { answer, session };
+        self.events.send(Event::Answer(self.server, self.id, outcome)).ok();
+
+        value
+    }
+}
+
+// The status is the final reply
+fn raw_command(conn: &mut DICTClient, line: &str) -> Result<(Vec<RawAnswer>, Reply), DICTError> {
+    let answers = conn.raw_command(line)?;
+
+    let last = answers.iter().rev().find_map(|answer| match answer {
+        RawAnswer::Reply(r) => Some(r.clone()),
+        RawAnswer::Text(_) => None
+    });
+
+    last.map(|r| (answers, r)).ok_or(DICTError::NoAnswer)
+}
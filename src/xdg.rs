@@ -0,0 +1,25 @@
+use std::env;
+use std::path::PathBuf;
+
+// The variable, or the default under the home directory
+fn base_dir(var: &str, default: &str) -> Option<PathBuf> {
+    match env::var_os(var) {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => Some(PathBuf::from(env::var_os("HOME")?).join(default))
+    }
+}
+
+/// $XDG_DATA_HOME/redict
+pub fn data_dir() -> Option<PathBuf> {
+    base_dir("XDG_DATA_HOME", ".local/share").map(|dir| dir.join("redict"))
+}
+
+/// $XDG_CACHE_HOME/redict
+pub fn cache_dir() -> Option<PathBuf> {
+    base_dir("XDG_CACHE_HOME", ".cache").map(|dir| dir.join("redict"))
+}
+
+/// $XDG_CONFIG_HOME/redict
+pub fn config_dir() -> Option<PathBuf> {
+    base_dir("XDG_CONFIG_HOME", ".config").map(|dir| dir.join("redict"))
+}
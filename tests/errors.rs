@@ -0,0 +1,116 @@
+use dictproto::{
+    connection::{DICTConnection, DICTError},
+    testing::{connect, MockReply, MockServer},
+    Database,
+};
+use std::time::{Duration, Instant};
+
+#[test]
+fn no_match() {
+    let server = MockServer::new()
+        .on(
+            "DEFINE \"*\" \"tii\"",
+            MockReply::error(552, "no match [d/m/c = 0/0/0; 0.000r 0.000u 0.000s]"),
+        )
+        .start()
+        .unwrap();
+
+    let mut conn = connect(&server);
+
+    match conn.define(Database::all(), String::from("tii")) {
+        Err(DICTError::SystemError(reply)) => assert_eq!(reply.status.to_string(), "552"),
+        other => panic!("Unexpected answer: {:?}", other),
+    }
+}
+
+#[test]
+fn unknown_command() {
+    let server = MockServer::new().start().unwrap();
+    let mut conn = connect(&server);
+
+    assert!(conn.show_db().is_err());
+}
+
+#[test]
+fn refused_banner() {
+    let server = MockServer::new()
+        .banner(MockReply::error(530, "access denied"))
+        .start()
+        .unwrap();
+
+    let mut conn = DICTConnection::new(server.connect().unwrap()).unwrap();
+    assert!(conn.start().is_err());
+}
+
+#[test]
+fn slow_response() {
+    let server = MockServer::new()
+        .on(
+            "DEFINE \"*\" \"ti\"",
+            MockReply::new()
+                .delay(Duration::from_millis(200))
+                .status(552, "no match"),
+        )
+        .start()
+        .unwrap();
+
+    let mut conn = connect(&server);
+
+    let start = Instant::now();
+    assert!(conn.define(Database::all(), String::from("ti")).is_err());
+    assert!(start.elapsed() >= Duration::from_millis(200));
+}
+
+#[test]
+fn abrupt_disconnect() {
+    let server = MockServer::new()
+        .on("DEFINE \"*\" \"ti\"", MockReply::new().disconnect())
+        .start()
+        .unwrap();
+
+    let mut conn = connect(&server);
+
+    match conn.define(Database::all(), String::from("ti")) {
+        Err(DICTError::ReplyError(_)) => {}
+        other => panic!("Unexpected answer: {:?}", other),
+    }
+}
+
+#[test]
+fn disconnect_in_text() {
+    let server = MockServer::new()
+        .on(
+            "DEFINE \"*\" \"ti\"",
+            MockReply::new()
+                .status(150, "1 definitions retrieved")
+                .status(151, "\"ti\" wn \"WordNet\"")
+                .raw("ti")
+                .disconnect(),
+        )
+        .start()
+        .unwrap();
+
+    let mut conn = connect(&server);
+
+    assert!(conn.define(Database::all(), String::from("ti")).is_err());
+}
+
+#[test]
+fn garbage_status_line() {
+    let server = MockServer::new()
+        .on("DEFINE \"*\" \"ti\"", MockReply::new().raw("garbage"))
+        .on("STATUS", MockReply::error(210, "status [d/m/c = 0/0/0]"))
+        .start()
+        .unwrap();
+
+    let mut conn = connect(&server);
+
+    match conn.define(Database::all(), String::from("ti")) {
+        Err(DICTError::ReplyError(_)) => {}
+        other => panic!("Unexpected answer: {:?}", other),
+    }
+
+    // The next reply is for the next command
+    let reply = conn.status().unwrap();
+    assert_eq!(reply.status.to_string(), "210");
+}
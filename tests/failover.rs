@@ -0,0 +1,101 @@
+use dictproto::{
+    client::{Backoff, DICTClient},
+    testing::{wordnet, MockHandle, MockReply, MockServer},
+    Database,
+};
+use std::time::Duration;
+
+fn fast_backoff() -> Backoff {
+    Backoff {
+        initial: Duration::from_millis(10),
+        max: Duration::from_millis(50),
+        attempts: 2,
+    }
+}
+
+fn client(servers: &[&MockHandle]) -> DICTClient {
+    let addr = servers[0].addr();
+    let mut client = DICTClient::new(&addr.ip().to_string(), addr.port()).backoff(fast_backoff());
+
+    for server in &servers[1..] {
+        let addr = server.addr();
+        client = client.mirror(&addr.ip().to_string(), addr.port());
+    }
+
+    client
+}
+
+#[test]
+fn connect_to_mirror() {
+    let down = MockServer::new().start().unwrap();
+    let mirror = MockServer::new()
+        .define("*", "ti", &[wordnet("ti")])
+        .start()
+        .unwrap();
+    let mut client = client(&[&down, &mirror]);
+    drop(down);
+
+    client.connect().unwrap();
+    assert_eq!(client.active_server().1, mirror.addr().port());
+    assert_eq!(client.reconnect_attempts(), 0);
+
+    let (defs, _) = client.define(Database::all(), String::from("ti")).unwrap();
+    assert_eq!(defs[0].text, vec!["ti"]);
+}
+
+#[test]
+fn skip_unavailable_server() {
+    let busy = MockServer::new()
+        .banner(MockReply::error(420, "server temporarily unavailable"))
+        .start()
+        .unwrap();
+    let mirror = MockServer::new().start().unwrap();
+    let mut client = client(&[&busy, &mirror]);
+
+    client.connect().unwrap();
+    assert_eq!(client.active_server().1, mirror.addr().port());
+    assert_eq!(mirror.received(), vec!["CLIENT \"redict\""]);
+}
+
+#[test]
+fn failover_on_shutdown() {
+    let primary = MockServer::new()
+        .on(
+            "DEFINE \"*\" \"ti\"",
+            MockReply::error(421, "server shutting down").disconnect(),
+        )
+        .start()
+        .unwrap();
+    let mirror = MockServer::new()
+        .define("*", "ti", &[wordnet("ti")])
+        .define("*", "tu", &[wordnet("tu")])
+        .start()
+        .unwrap();
+    let mut client = client(&[&primary, &mirror]);
+
+    client.connect().unwrap();
+    assert_eq!(client.active_server().1, primary.addr().port());
+
+    let (defs, _) = client.define(Database::all(), String::from("ti")).unwrap();
+    assert_eq!(defs[0].text, vec!["ti"]);
+    assert_eq!(client.active_server().1, mirror.addr().port());
+
+    // The healthy server is kept for the next commands
+    client.define(Database::all(), String::from("tu")).unwrap();
+    assert_eq!(
+        primary.received(),
+        vec!["CLIENT \"redict\"", "DEFINE \"*\" \"ti\""]
+    );
+}
+
+#[test]
+fn all_down() {
+    let first = MockServer::new().start().unwrap();
+    let second = MockServer::new().start().unwrap();
+    let mut client = client(&[&first, &second]);
+    drop(first);
+    drop(second);
+
+    assert!(client.connect().is_err());
+    assert_eq!(client.reconnect_attempts(), 2);
+}
@@ -0,0 +1,60 @@
+use dictproto::{
+    client::DICTClient,
+    connection::DICTConnection,
+    testing::{MockReply, MockServer},
+    Database,
+};
+
+#[test]
+fn banner_capabilities() {
+    let server = MockServer::new()
+        .banner(MockReply::new().status(220, "mock.test dictd <auth.mime> <1.1@mock.test>"))
+        .start()
+        .unwrap();
+    let addr = server.addr();
+
+    let mut client = DICTClient::new(&addr.ip().to_string(), addr.port());
+    client.connect().unwrap();
+
+    assert_eq!(client.msg_id(), "<1.1@mock.test>");
+    assert_eq!(client.capabilities(), ["auth", "mime"]);
+}
+
+#[test]
+fn show_info() {
+    let server = MockServer::new()
+        .show_info("wn", &["WordNet (r) 3.0", ".dotted"])
+        .show_info("empty", &[] as &[&str])
+        .start()
+        .unwrap();
+    let mut conn = DICTConnection::new(server.connect().unwrap()).unwrap();
+    conn.start().unwrap();
+
+    let (text, _) = conn.show_info(Database::from(String::from("wn"))).unwrap();
+    assert_eq!(text, vec!["WordNet (r) 3.0", ".dotted"]);
+
+    let (text, _) = conn
+        .show_info(Database::from(String::from("empty")))
+        .unwrap();
+    assert!(text.is_empty());
+}
+
+#[test]
+fn show_server_and_status() {
+    let server = MockServer::new()
+        .show_server(&["dictd 1.12.1 on Linux", "On mock.test: up 1 day"])
+        .on(
+            "STATUS",
+            MockReply::error(210, "status [d/m/c = 0/0/0; 10.000r 0.000u 0.000s]"),
+        )
+        .start()
+        .unwrap();
+    let mut conn = DICTConnection::new(server.connect().unwrap()).unwrap();
+    conn.start().unwrap();
+
+    let (text, _) = conn.show_server().unwrap();
+    assert_eq!(text[0], "dictd 1.12.1 on Linux");
+
+    let status = conn.status().unwrap();
+    assert!(status.text.starts_with("status [d/m/c"));
+}
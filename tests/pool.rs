@@ -0,0 +1,143 @@
+use dictproto::{
+    connection::DICTError,
+    pool::{DICTPool, PoolConfig},
+    testing::{wordnet, MockHandle, MockReply, MockServer},
+    Database,
+};
+use std::thread;
+use std::time::Duration;
+
+const DEFINE_TI: &str = "DEFINE \"*\" \"ti\"";
+
+fn pool(server: &MockHandle, config: PoolConfig) -> DICTPool {
+    let addr = server.addr();
+    DICTPool::new(&addr.ip().to_string(), addr.port(), config)
+}
+
+fn connections(server: &MockHandle) -> usize {
+    server
+        .received()
+        .iter()
+        .filter(|c| c.starts_with("CLIENT"))
+        .count()
+}
+
+#[test]
+fn parallel_lookups() {
+    let server = MockServer::new()
+        .on(
+            DEFINE_TI,
+            MockReply::new()
+                .delay(Duration::from_millis(20))
+                .raw("150 1 definitions retrieved")
+                .status(151, "\"ti\" wn \"WordNet\"")
+                .text(&["ti"])
+                .status(250, "ok"),
+        )
+        .start()
+        .unwrap();
+
+    let pool = pool(
+        &server,
+        PoolConfig {
+            max_size: 3,
+            ..PoolConfig::default()
+        },
+    );
+
+    let workers: Vec<_> = (0..8)
+        .map(|_| {
+            let pool = pool.clone();
+            thread::spawn(move || {
+                for _ in 0..3 {
+                    let (defs, _) = pool.define(Database::all(), String::from("ti")).unwrap();
+                    assert_eq!(defs[0].text, vec!["ti"]);
+                }
+            })
+        })
+        .collect();
+
+    for w in workers {
+        w.join().unwrap();
+    }
+
+    assert!(connections(&server) <= 3);
+    assert_eq!(pool.size(), pool.idle());
+}
+
+#[test]
+fn reuses_connections() {
+    let server = MockServer::new()
+        .define("*", "ti", &[wordnet("ti")])
+        .start()
+        .unwrap();
+    let pool = pool(&server, PoolConfig::default());
+
+    for _ in 0..5 {
+        pool.define(Database::all(), String::from("ti")).unwrap();
+    }
+
+    assert_eq!(connections(&server), 1);
+    assert_eq!(pool.size(), 1);
+}
+
+#[test]
+fn evicts_idle_connections() {
+    let server = MockServer::new()
+        .define("*", "ti", &[wordnet("ti")])
+        .start()
+        .unwrap();
+    let pool = pool(
+        &server,
+        PoolConfig {
+            idle_timeout: Duration::from_millis(50),
+            ..PoolConfig::default()
+        },
+    );
+
+    pool.define(Database::all(), String::from("ti")).unwrap();
+    thread::sleep(Duration::from_millis(100));
+    pool.define(Database::all(), String::from("ti")).unwrap();
+
+    assert_eq!(connections(&server), 2);
+    assert_eq!(pool.size(), 1);
+}
+
+#[test]
+fn replaces_dead_connections() {
+    let server = MockServer::new()
+        .on(
+            DEFINE_TI,
+            MockReply::definitions("ti", &[wordnet("ti")]).disconnect(),
+        )
+        .start()
+        .unwrap();
+    let pool = pool(&server, PoolConfig::default());
+
+    pool.define(Database::all(), String::from("ti")).unwrap();
+    // Let the server close the connection
+    thread::sleep(Duration::from_millis(50));
+    pool.define(Database::all(), String::from("ti")).unwrap();
+
+    assert_eq!(connections(&server), 2);
+}
+
+#[test]
+fn checkout_timeout() {
+    let server = MockServer::new().start().unwrap();
+    let pool = pool(
+        &server,
+        PoolConfig {
+            max_size: 1,
+            checkout_timeout: Some(Duration::from_millis(50)),
+            ..PoolConfig::default()
+        },
+    );
+
+    let _busy = pool.get().unwrap();
+    match pool.get() {
+        Err(DICTError::Timeout) => {}
+        Err(e) => panic!("Unexpected error: {:?}", e),
+        Ok(_) => panic!("Got more connections than allowed"),
+    }
+}
@@ -0,0 +1,65 @@
+use dictproto::{
+    connection::RawAnswer,
+    testing::{connect, MockReply, MockServer},
+};
+
+fn replies(answers: &[RawAnswer]) -> Vec<String> {
+    answers
+        .iter()
+        .map(|a| match a {
+            RawAnswer::Reply(r) => r.to_string(),
+            RawAnswer::Text(t) => format!("[{}]", t.join("|")),
+        })
+        .collect()
+}
+
+#[test]
+fn text_then_reply() {
+    let server = MockServer::new()
+        .on(
+            "SHOW SERVER",
+            MockReply::new()
+                .status(114, "server information follows")
+                .text(&["dictd 1.12.1", ".hidden"])
+                .status(250, "ok"),
+        )
+        .start()
+        .unwrap();
+    let mut conn = connect(&server);
+
+    let answers = conn.raw_command("SHOW SERVER").unwrap();
+    assert_eq!(
+        replies(&answers),
+        vec![
+            "114 server information follows",
+            "[dictd 1.12.1|.hidden]",
+            "250 ok"
+        ]
+    );
+}
+
+#[test]
+fn negative_reply() {
+    let server = MockServer::new().start().unwrap();
+    let mut conn = connect(&server);
+
+    let answers = conn.raw_command("FOO bar").unwrap();
+    assert_eq!(answers.len(), 1);
+    assert!(replies(&answers)[0].starts_with("500"));
+
+    // The connection is still usable
+    assert!(conn.show_db().is_err());
+    assert_eq!(server.received(), vec!["FOO bar", "SHOW DATABASES"]);
+}
+
+#[test]
+fn only_one_line() {
+    let server = MockServer::new()
+        .on("STATUS", MockReply::error(210, "status"))
+        .start()
+        .unwrap();
+    let mut conn = connect(&server);
+
+    conn.raw_command("STATUS\r\nQUIT").unwrap();
+    assert_eq!(server.received(), vec!["STATUS"]);
+}
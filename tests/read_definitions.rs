@@ -0,0 +1,75 @@
+use dictproto::{
+    connection::DICTConnection,
+    testing::{definition, MockServer},
+    Database,
+};
+
+#[test]
+fn read_definitions() {
+    let server = MockServer::new()
+        .define(
+            "*",
+            "ti",
+            &[
+                definition(
+                    "gcide",
+                    "The Collaborative International Dictionary of English",
+                    &["Ti", "  n. a note"],
+                ),
+                definition(
+                    "wn",
+                    "WordNet (r) 3.0 (2006)",
+                    &["ti", "  n 1: the syllable naming the seventh note"],
+                ),
+            ],
+        )
+        .start()
+        .unwrap();
+
+    let mut conn = DICTConnection::new(server.connect().unwrap()).unwrap();
+    let (banner, _) = conn.start().unwrap();
+    assert_eq!(banner.msg_id, "<1.1@mock.test>");
+
+    let (defs, _) = conn.define(Database::all(), String::from("ti")).unwrap();
+
+    assert_eq!(defs.len(), 2);
+    assert_eq!(defs[0].source.name, "gcide");
+    assert_eq!(defs[0].text, vec!["Ti", "  n. a note"]);
+    assert_eq!(defs[1].source.desc, "WordNet (r) 3.0 (2006)");
+
+    assert_eq!(server.received(), vec!["DEFINE \"*\" \"ti\""]);
+}
+
+#[test]
+fn read_definitions_twice() {
+    let server = MockServer::new()
+        .define("wn", "ti", &[definition("wn", "WordNet", &["ti"])])
+        .start()
+        .unwrap();
+
+    let mut conn = DICTConnection::new(server.connect().unwrap()).unwrap();
+    conn.start().unwrap();
+    conn.client(String::from("redict")).unwrap();
+
+    for _ in 0..2 {
+        let (defs, _) = conn
+            .define(Database::from(String::from("wn")), String::from("ti"))
+            .unwrap();
+        assert_eq!(defs.len(), 1);
+    }
+}
+
+#[test]
+fn skip_banner_with_next() {
+    let server = MockServer::new()
+        .define("*", "ti", &[definition("wn", "WordNet", &["ti"])])
+        .start()
+        .unwrap();
+
+    // As done in examples/read_definitions.rs
+    let mut conn = DICTConnection::new(server.connect().unwrap()).unwrap();
+    conn.next();
+
+    let (defs, _) = conn.define(Database::all(), String::from("ti")).unwrap();
+    assert_eq!(defs[0].source.desc, "WordNet");
+}
@@ -0,0 +1,67 @@
+use dictproto::{connection::DICTConnection, testing::MockServer, Database, Match, Strategy};
+
+fn found(db: &str, word: &str) -> Match {
+    Match {
+        source: Database::from(String::from(db)),
+        word: String::from(word),
+    }
+}
+
+#[test]
+fn read_matches() {
+    let server = MockServer::new()
+        .match_db(
+            "*",
+            ".",
+            "ti",
+            &[
+                found("gcide", "Ti"),
+                found("wn", "ti"),
+                found("jargon", "tick"),
+            ],
+        )
+        .start()
+        .unwrap();
+
+    let mut conn = DICTConnection::new(server.connect().unwrap()).unwrap();
+    conn.start().unwrap();
+
+    let (matches, _) = conn
+        .match_db(Database::all(), Strategy::default(), String::from("ti"))
+        .unwrap();
+
+    let words: Vec<(&str, &str)> = matches
+        .iter()
+        .map(|m| (m.source.name.as_str(), m.word.as_str()))
+        .collect();
+    assert_eq!(
+        words,
+        vec![("gcide", "Ti"), ("wn", "ti"), ("jargon", "tick")]
+    );
+}
+
+#[test]
+fn read_databases_and_strategies() {
+    let server = MockServer::new()
+        .show_db(&[Database {
+            name: String::from("wn"),
+            desc: String::from("WordNet"),
+        }])
+        .show_strat(&[Strategy {
+            name: String::from("prefix"),
+            desc: String::from("Match prefixes"),
+        }])
+        .start()
+        .unwrap();
+
+    let mut conn = DICTConnection::new(server.connect().unwrap()).unwrap();
+    conn.start().unwrap();
+
+    let (dbs, _) = conn.show_db().unwrap();
+    assert_eq!(dbs[0].name, "wn");
+    assert_eq!(dbs[0].desc, "WordNet");
+
+    let (strats, _) = conn.show_strat().unwrap();
+    assert_eq!(strats[0].name, "prefix");
+    assert_eq!(strats[0].desc, "Match prefixes");
+}
@@ -0,0 +1,145 @@
+use dictproto::{
+    client::{Backoff, DICTClient},
+    connection::DICTError,
+    testing::{wordnet, MockReply, MockServer},
+    Database,
+};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn fast_backoff() -> Backoff {
+    Backoff {
+        initial: Duration::from_millis(10),
+        max: Duration::from_millis(50),
+        attempts: 3,
+    }
+}
+
+const DEFINE_TI: &str = "DEFINE \"*\" \"ti\"";
+
+#[test]
+fn reconnect_after_disconnect() {
+    let server = MockServer::new()
+        .on(DEFINE_TI, MockReply::new().disconnect())
+        .on(DEFINE_TI, MockReply::definitions("ti", &[wordnet("ti")]))
+        .start()
+        .unwrap();
+
+    let addr = server.addr();
+    let mut client = DICTClient::new(&addr.ip().to_string(), addr.port()).backoff(fast_backoff());
+    client.connect().unwrap();
+    assert_eq!(client.reconnect_attempts(), 0);
+
+    let (defs, _) = client.define(Database::all(), String::from("ti")).unwrap();
+    assert_eq!(defs[0].text, vec!["ti"]);
+    assert_eq!(client.reconnect_attempts(), 1);
+
+    assert_eq!(
+        server.received(),
+        vec![
+            "CLIENT \"redict\"",
+            DEFINE_TI,
+            "CLIENT \"redict\"",
+            DEFINE_TI
+        ]
+    );
+}
+
+#[test]
+fn reconnect_after_421() {
+    let server = MockServer::new()
+        .on(
+            DEFINE_TI,
+            MockReply::error(421, "server shutting down").disconnect(),
+        )
+        .on(DEFINE_TI, MockReply::definitions("ti", &[wordnet("ti")]))
+        .start()
+        .unwrap();
+
+    let addr = server.addr();
+    let mut client = DICTClient::new(&addr.ip().to_string(), addr.port()).backoff(fast_backoff());
+    client.connect().unwrap();
+
+    assert!(client.define(Database::all(), String::from("ti")).is_ok());
+}
+
+#[test]
+fn no_reconnect_on_errors() {
+    let server = MockServer::new()
+        .on(DEFINE_TI, MockReply::error(552, "no match"))
+        .start()
+        .unwrap();
+
+    let addr = server.addr();
+    let mut client = DICTClient::new(&addr.ip().to_string(), addr.port()).backoff(fast_backoff());
+    client.connect().unwrap();
+
+    match client.define(Database::all(), String::from("ti")) {
+        Err(DICTError::SystemError(_)) => {}
+        other => panic!("Unexpected answer: {:?}", other),
+    }
+    assert_eq!(client.reconnect_attempts(), 0);
+}
+
+#[test]
+fn replays_auth() {
+    // md5("<1.1@mock.test>" + "secret")
+    let auth = format!("AUTH user {:x}", md5::compute("<1.1@mock.test>secret"));
+
+    let server = MockServer::new()
+        .on(&auth, MockReply::error(230, "authenticated"))
+        .on(DEFINE_TI, MockReply::new().disconnect())
+        .on(DEFINE_TI, MockReply::error(552, "no match"))
+        .start()
+        .unwrap();
+
+    let addr = server.addr();
+    let mut client = DICTClient::new(&addr.ip().to_string(), addr.port())
+        .auth("user", "secret")
+        .backoff(fast_backoff());
+    client.connect().unwrap();
+    client.define(Database::all(), String::from("ti")).ok();
+
+    let received = server.received();
+    assert_eq!(received.iter().filter(|c| **c == auth).count(), 2);
+}
+
+#[test]
+fn gives_up() {
+    let server = MockServer::new().start().unwrap();
+    let addr = server.addr();
+    drop(server);
+
+    let mut client = DICTClient::new(&addr.ip().to_string(), addr.port()).backoff(fast_backoff());
+
+    assert!(client.connect().is_err());
+    assert_eq!(client.reconnect_attempts(), 3);
+}
+
+#[test]
+fn cancel_while_waiting() {
+    let server = MockServer::new().start().unwrap();
+    let addr = server.addr();
+    drop(server);
+
+    let mut client = DICTClient::new(&addr.ip().to_string(), addr.port()).backoff(Backoff {
+        initial: Duration::from_secs(5),
+        max: Duration::from_secs(5),
+        attempts: 5,
+    });
+    let handle = client.cancel_handle();
+
+    let canceller = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        handle.cancel();
+    });
+
+    let start = Instant::now();
+    match client.connect() {
+        Err(DICTError::Cancelled) => {}
+        other => panic!("Unexpected answer: {:?}", other),
+    }
+    assert!(start.elapsed() < Duration::from_secs(2));
+
+    canceller.join().unwrap();
+}
@@ -0,0 +1,156 @@
+use dictproto::{
+    connection::{DICTConnection, DICTError, Timeouts},
+    testing::{MockHandle, MockReply, MockServer},
+    Database,
+};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn connect(server: &MockHandle, timeouts: Timeouts) -> DICTConnection {
+    let mut conn = DICTConnection::connect(server.addr(), timeouts).unwrap();
+    conn.start().unwrap();
+    conn
+}
+
+fn slow_define(delay: Duration) -> MockReply {
+    MockReply::new().delay(delay).status(552, "no match")
+}
+
+#[test]
+fn read_timeout() {
+    let server = MockServer::new()
+        .on("DEFINE \"*\" \"ti\"", slow_define(Duration::from_secs(5)))
+        .start()
+        .unwrap();
+
+    let mut conn = connect(
+        &server,
+        Timeouts {
+            read: Some(Duration::from_millis(100)),
+            ..Timeouts::default()
+        },
+    );
+
+    let start = Instant::now();
+    match conn.define(Database::all(), String::from("ti")) {
+        Err(DICTError::Timeout) => {}
+        other => panic!("Unexpected answer: {:?}", other),
+    }
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert!(!conn.is_usable());
+}
+
+#[test]
+fn command_deadline() {
+    // Every line arrives before the read timeout, but the whole answer is
+    // too slow.
+    let mut reply = MockReply::new().status(150, "1 definitions retrieved");
+    reply = reply.status(151, "\"ti\" wn \"WordNet\"");
+    for _ in 0..20 {
+        reply = reply.delay(Duration::from_millis(50)).raw("ti");
+    }
+    let reply = reply.raw(".").status(250, "ok");
+
+    let server = MockServer::new()
+        .on("DEFINE \"*\" \"ti\"", reply)
+        .start()
+        .unwrap();
+
+    let mut conn = connect(
+        &server,
+        Timeouts {
+            read: Some(Duration::from_millis(500)),
+            command: Some(Duration::from_millis(300)),
+            ..Timeouts::default()
+        },
+    );
+
+    match conn.define(Database::all(), String::from("ti")) {
+        Err(DICTError::Timeout) => {}
+        other => panic!("Unexpected answer: {:?}", other),
+    }
+}
+
+#[test]
+fn fast_enough() {
+    let server = MockServer::new()
+        .on(
+            "DEFINE \"*\" \"ti\"",
+            slow_define(Duration::from_millis(50)),
+        )
+        .start()
+        .unwrap();
+
+    let mut conn = connect(
+        &server,
+        Timeouts {
+            read: Some(Duration::from_secs(2)),
+            command: Some(Duration::from_secs(2)),
+            ..Timeouts::default()
+        },
+    );
+
+    match conn.define(Database::all(), String::from("ti")) {
+        Err(DICTError::SystemError(_)) => {}
+        other => panic!("Unexpected answer: {:?}", other),
+    }
+    assert!(conn.is_usable());
+}
+
+#[test]
+fn deadline_ends_with_response() {
+    let server = MockServer::new()
+        .on(
+            "DEFINE \"*\" \"ti\"",
+            MockReply::new()
+                .status(552, "no match")
+                .delay(Duration::from_millis(400))
+                .status(210, "late"),
+        )
+        .start()
+        .unwrap();
+
+    let mut conn = connect(
+        &server,
+        Timeouts {
+            read: Some(Duration::from_secs(2)),
+            command: Some(Duration::from_millis(300)),
+            ..Timeouts::default()
+        },
+    );
+
+    assert!(conn.define(Database::all(), String::from("ti")).is_err());
+
+    // Read past the deadline of the define, which is over
+    match conn.next() {
+        Some(Ok(packet)) => assert_eq!(packet.1.status.to_string(), "210"),
+        other => panic!("Unexpected answer: {:?}", other),
+    }
+    assert!(conn.is_usable());
+}
+
+#[test]
+fn cancel_define() {
+    let server = MockServer::new()
+        .on("DEFINE \"*\" \"ti\"", slow_define(Duration::from_secs(5)))
+        .start()
+        .unwrap();
+
+    let mut conn = connect(&server, Timeouts::default());
+    let handle = conn.cancel_handle();
+
+    let canceller = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        handle.cancel();
+    });
+
+    let start = Instant::now();
+    match conn.define(Database::all(), String::from("ti")) {
+        Err(DICTError::Cancelled) => {}
+        other => panic!("Unexpected answer: {:?}", other),
+    }
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert!(!conn.is_usable());
+
+    canceller.join().unwrap();
+}
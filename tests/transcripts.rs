@@ -0,0 +1,91 @@
+use dictproto::{
+    connection::DICTConnection,
+    reply::Reply,
+    status::{Category, ReplyKind, Status},
+    testing::MockServer,
+    transcript::{Recorder, Transcript},
+    Database, Definition,
+};
+use std::fs::File;
+
+fn transcript(name: &str) -> Transcript {
+    Transcript::open(format!(
+        "{}/tests/transcripts/{}",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+    .unwrap()
+}
+
+#[test]
+fn replay_replies() {
+    let mut replay = transcript("define_ti.txt").replay();
+
+    let banner = Reply::from_reader(&mut replay).unwrap();
+    assert!(banner.status.is_start());
+
+    let ok = Reply::from_reader(&mut replay).unwrap();
+    assert_eq!(
+        ok.status,
+        Status(ReplyKind::PositiveCompletion, Category::System, 0)
+    );
+}
+
+#[test]
+fn replay_define() {
+    let mut conn = DICTConnection::replay(&transcript("define_ti.txt"));
+
+    let (banner, _) = conn.start().unwrap();
+    assert_eq!(banner.msg_id, "<74.26133.1618064395@dict.dict.org>");
+    assert_eq!(banner.capabilities, vec!["auth", "mime"]);
+
+    conn.client(String::from("redict")).unwrap();
+
+    let (defs, _) = conn.define(Database::all(), String::from("ti")).unwrap();
+    assert_eq!(defs.len(), 2);
+    assert_eq!(defs[0].text[0], "Ti \\Ti\\, n.");
+    assert_eq!(defs[1].source.name, "wn");
+    assert_eq!(defs[1].text.len(), 3);
+}
+
+#[test]
+fn record_then_replay() {
+    let path = std::env::temp_dir().join(format!("redict-transcript-{}.txt", std::process::id()));
+
+    let server = MockServer::new()
+        .define(
+            "wn",
+            "ti",
+            &[Definition {
+                source: Database {
+                    name: String::from("wn"),
+                    desc: String::from("WordNet"),
+                },
+                text: vec![String::from("ti")],
+            }],
+        )
+        .start()
+        .unwrap();
+
+    {
+        let recorder = Recorder::new(File::create(&path).unwrap()).unwrap();
+        let mut conn = DICTConnection::recorded(server.connect().unwrap(), &recorder).unwrap();
+        conn.start().unwrap();
+        conn.define(Database::from(String::from("wn")), String::from("ti"))
+            .unwrap();
+    }
+
+    let recorded = Transcript::open(&path).unwrap();
+    std::fs::remove_file(&path).ok();
+
+    assert!(String::from_utf8(recorded.sent())
+        .unwrap()
+        .starts_with("DEFINE \"wn\" \"ti\"\r\n"));
+
+    let mut conn = DICTConnection::replay(&recorded);
+    conn.start().unwrap();
+    let (defs, _) = conn
+        .define(Database::from(String::from("wn")), String::from("ti"))
+        .unwrap();
+    assert_eq!(defs[0].text, vec!["ti"]);
+}